@@ -0,0 +1,565 @@
+use crate::{chunk::Chunk, error::Error, ihdr::Ihdr, validate::Diagnostic};
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes(bytes[at..at + 2].try_into().unwrap())
+}
+
+fn check_length(chunk_type: &str, value: &[u8], length: usize) -> Result<(), Error> {
+    if value.len() != length {
+        return Err(Error::InvalidData(format!(
+            "{} needs {} bytes, got {} byte(s)",
+            chunk_type,
+            length,
+            value.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// The `acTL` chunk, which marks a PNG as animated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    /// How often to play the animation, 0 for forever.
+    pub num_plays: u32,
+}
+
+impl TryFrom<&[u8]> for AnimationControl {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        check_length("acTL", value, Self::LENGTH)?;
+
+        Ok(Self {
+            num_frames: read_u32(value, 0),
+            num_plays: read_u32(value, 4),
+        })
+    }
+}
+
+impl AnimationControl {
+    pub const LENGTH: usize = 8;
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [self.num_frames.to_be_bytes(), self.num_plays.to_be_bytes()].concat()
+    }
+}
+
+/// What happens to a frame's region before the next frame is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    None,
+    /// Clear the region to fully transparent black.
+    Background,
+    /// Restore the region to what it was before the frame.
+    Previous,
+}
+
+/// How a frame is drawn over the output buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    /// Replace the region.
+    Source,
+    /// Alpha-composite over the region.
+    Over,
+}
+
+impl std::fmt::Display for DisposeOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Background => write!(f, "background"),
+            Self::Previous => write!(f, "previous"),
+        }
+    }
+}
+
+impl std::fmt::Display for BlendOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Source => write!(f, "source"),
+            Self::Over => write!(f, "over"),
+        }
+    }
+}
+
+/// The `fcTL` chunk that starts a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    /// 0 means 100.
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl TryFrom<&[u8]> for FrameControl {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        check_length("fcTL", value, Self::LENGTH)?;
+
+        let dispose_op = match value[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            op => return Err(Error::InvalidData(format!("Invalid dispose op: {}", op))),
+        };
+        let blend_op = match value[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            op => return Err(Error::InvalidData(format!("Invalid blend op: {}", op))),
+        };
+
+        Ok(Self {
+            sequence_number: read_u32(value, 0),
+            width: read_u32(value, 4),
+            height: read_u32(value, 8),
+            x_offset: read_u32(value, 12),
+            y_offset: read_u32(value, 16),
+            delay_num: read_u16(value, 20),
+            delay_den: read_u16(value, 22),
+            dispose_op,
+            blend_op,
+        })
+    }
+}
+
+impl FrameControl {
+    pub const LENGTH: usize = 26;
+
+    /// How long the frame is shown.
+    pub fn delay_seconds(&self) -> f64 {
+        let den = match self.delay_den {
+            0 => 100,
+            den => den,
+        };
+
+        self.delay_num as f64 / den as f64
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            self.sequence_number.to_be_bytes().as_slice(),
+            &self.width.to_be_bytes(),
+            &self.height.to_be_bytes(),
+            &self.x_offset.to_be_bytes(),
+            &self.y_offset.to_be_bytes(),
+            &self.delay_num.to_be_bytes(),
+            &self.delay_den.to_be_bytes(),
+            &[self.dispose_op as u8, self.blend_op as u8],
+        ]
+        .concat()
+    }
+}
+
+/// Sequence number of an `fdAT` chunk, the frame data follows it.
+pub fn fdat_sequence_number(data: &[u8]) -> Result<u32, Error> {
+    if data.len() < 4 {
+        return Err(Error::InvalidData(format!(
+            "fdAT needs at least 4 bytes, got {} byte(s)",
+            data.len()
+        )));
+    }
+
+    Ok(read_u32(data, 0))
+}
+
+/// One frame: its `fcTL` and the `IDAT` or `fdAT` chunks after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub control_index: usize,
+    pub control: FrameControl,
+    pub data_indices: Vec<usize>,
+    /// Whether the frame is the default image, stored in `IDAT`.
+    pub is_default_image: bool,
+}
+
+/// The animation of an APNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+}
+
+impl std::fmt::Display for Animation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} frame(s), ", self.control.num_frames)?;
+
+        match self.control.num_plays {
+            0 => write!(f, "loops forever"),
+            plays => write!(f, "plays {} time(s)", plays),
+        }
+    }
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let control = &self.control;
+
+        write!(
+            f,
+            "[chunk {}] {}x{} at ({}, {}), delay {}s, dispose {}, blend {}, {} data chunk(s){}",
+            self.control_index,
+            control.width,
+            control.height,
+            control.x_offset,
+            control.y_offset,
+            control.delay_seconds(),
+            control.dispose_op,
+            control.blend_op,
+            self.data_indices.len(),
+            if self.is_default_image {
+                ", default image"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+pub(crate) fn is_frame_data(chunk_type: &str) -> bool {
+    chunk_type == "IDAT" || chunk_type == "fdAT"
+}
+
+/// Follows chunk types one by one to tell whether the chunks so far end inside a frame, where
+/// a chunk placed next would split an `fcTL` from its data.
+#[derive(Debug, Default)]
+pub(crate) struct FrameTracker {
+    in_frame: bool,
+}
+
+impl FrameTracker {
+    pub(crate) fn push(&mut self, chunk_type: &str) {
+        self.in_frame = chunk_type == "fcTL" || (self.in_frame && is_frame_data(chunk_type));
+    }
+
+    /// Whether placing a chunk before one of type `next` would split a frame.
+    pub(crate) fn splits(&self, next: &str) -> bool {
+        self.in_frame && is_frame_data(next)
+    }
+}
+
+/// The animation of `chunks`, `None` if there is no `acTL`.
+pub fn animation(chunks: &[Chunk]) -> Result<Option<Animation>, Error> {
+    let control = match chunks.iter().find(|c| c.chunk_type().to_string() == "acTL") {
+        Some(chunk) => AnimationControl::try_from(chunk.data())?,
+        None => return Ok(None),
+    };
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut tracker = FrameTracker::default();
+
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let chunk_type = chunk.chunk_type().to_string();
+
+        match chunk_type.as_str() {
+            "fcTL" => frames.push(Frame {
+                control_index: idx,
+                control: FrameControl::try_from(chunk.data())?,
+                data_indices: Vec::new(),
+                is_default_image: false,
+            }),
+            t if tracker.splits(t) => {
+                let frame = frames.last_mut().unwrap();
+
+                frame.data_indices.push(idx);
+                frame.is_default_image |= t == "IDAT";
+            }
+            _ => (),
+        }
+
+        tracker.push(&chunk_type);
+    }
+
+    Ok(Some(Animation { control, frames }))
+}
+
+/// Check the animation chunks: `acTL` placement and frame count, frame regions, and that
+/// `fcTL` and `fdAT` sequence numbers count up from 0 without gaps.
+pub fn validate(chunks: &[Chunk]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+    let first_of = |t: &str| types.iter().position(|x| x == t);
+    let actl = first_of("acTL");
+
+    if actl.is_none() {
+        for t in ["fcTL", "fdAT"] {
+            if let Some(idx) = first_of(t) {
+                diagnostics.push(Diagnostic::error(Some(idx), format!("{} without acTL", t)));
+            }
+        }
+
+        return diagnostics;
+    }
+
+    let actl = actl.unwrap();
+    let control = match AnimationControl::try_from(chunks[actl].data()) {
+        Ok(control) => Some(control),
+        Err(err) => {
+            diagnostics.push(Diagnostic::error(Some(actl), err.to_string()));
+            None
+        }
+    };
+
+    if first_of("IDAT").is_some_and(|idat| idat < actl) {
+        diagnostics.push(Diagnostic::error(
+            Some(actl),
+            "acTL must come before IDAT".into(),
+        ));
+    }
+
+    if let Some(idx) = (actl + 1..types.len()).find(|&i| types[i] == "acTL") {
+        diagnostics.push(Diagnostic::error(
+            Some(idx),
+            "acTL must not appear more than once".into(),
+        ));
+    }
+
+    let ihdr = chunks.first().and_then(|c| Ihdr::try_from(c).ok());
+    let mut expected_sequence = 0;
+    let mut num_frames = 0;
+    let mut tracker = FrameTracker::default();
+
+    for (idx, (chunk, t)) in chunks.iter().zip(&types).enumerate() {
+        let sequence_number = match t.as_str() {
+            "fcTL" => match FrameControl::try_from(chunk.data()) {
+                Ok(control) => {
+                    num_frames += 1;
+
+                    if let Some(ihdr) = &ihdr {
+                        let fits = control.width > 0
+                            && control.height > 0
+                            && control.x_offset as u64 + control.width as u64 <= ihdr.width as u64
+                            && control.y_offset as u64 + control.height as u64
+                                <= ihdr.height as u64;
+
+                        if !fits {
+                            diagnostics.push(Diagnostic::error(
+                                Some(idx),
+                                format!(
+                                    "Frame {}x{} at ({}, {}) does not fit the {}x{} image",
+                                    control.width,
+                                    control.height,
+                                    control.x_offset,
+                                    control.y_offset,
+                                    ihdr.width,
+                                    ihdr.height
+                                ),
+                            ));
+                        }
+
+                        let is_default_image =
+                            types.get(idx + 1).map(String::as_str) == Some("IDAT");
+
+                        if is_default_image
+                            && (
+                                control.width,
+                                control.height,
+                                control.x_offset,
+                                control.y_offset,
+                            ) != (ihdr.width, ihdr.height, 0, 0)
+                        {
+                            diagnostics.push(Diagnostic::error(
+                                Some(idx),
+                                "The fcTL of the default image must cover the whole image".into(),
+                            ));
+                        }
+                    }
+
+                    Some(control.sequence_number)
+                }
+                Err(err) => {
+                    diagnostics.push(Diagnostic::error(Some(idx), err.to_string()));
+                    None
+                }
+            },
+            "fdAT" => {
+                if !tracker.splits(t) {
+                    diagnostics.push(Diagnostic::error(
+                        Some(idx),
+                        "fdAT does not follow an fcTL".into(),
+                    ));
+                }
+
+                match fdat_sequence_number(chunk.data()) {
+                    Ok(sequence_number) => Some(sequence_number),
+                    Err(err) => {
+                        diagnostics.push(Diagnostic::error(Some(idx), err.to_string()));
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        if let Some(sequence_number) = sequence_number {
+            if sequence_number != expected_sequence {
+                diagnostics.push(Diagnostic::error(
+                    Some(idx),
+                    format!(
+                        "{} sequence number is {}, expect {}",
+                        t, sequence_number, expected_sequence
+                    ),
+                ));
+            }
+
+            expected_sequence = sequence_number.wrapping_add(1);
+        }
+
+        tracker.push(t);
+    }
+
+    if let Some(control) = control {
+        if control.num_frames == 0 {
+            diagnostics.push(Diagnostic::error(Some(actl), "acTL has 0 frames".into()));
+        } else if control.num_frames != num_frames {
+            diagnostics.push(Diagnostic::error(
+                Some(actl),
+                format!(
+                    "acTL has {} frame(s), found {} fcTL",
+                    control.num_frames, num_frames
+                ),
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk_type::ChunkType, validate::Severity};
+    use std::str::FromStr;
+
+    fn fctl(sequence_number: u32, width: u32) -> FrameControl {
+        FrameControl {
+            sequence_number,
+            width,
+            height: 2,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 1,
+            delay_den: 10,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Over,
+        }
+    }
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn fdat(sequence_number: u32) -> Chunk {
+        chunk(
+            "fdAT",
+            [sequence_number.to_be_bytes(), [1, 2, 3, 4]].concat(),
+        )
+    }
+
+    /// 2x2 APNG of two frames, the default image is the first.
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            chunk("IHDR", vec![0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0]),
+            chunk(
+                "acTL",
+                AnimationControl {
+                    num_frames: 2,
+                    num_plays: 0,
+                }
+                .as_bytes(),
+            ),
+            chunk("fcTL", fctl(0, 2).as_bytes()),
+            chunk("IDAT", vec![1, 2, 3]),
+            chunk("fcTL", fctl(1, 1).as_bytes()),
+            fdat(2),
+            fdat(3),
+            chunk("IEND", vec![]),
+        ]
+    }
+
+    fn errors(chunks: &[Chunk]) -> Vec<Diagnostic> {
+        validate(chunks)
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect()
+    }
+
+    #[test]
+    fn test_frame_control_round_trip() {
+        let control = fctl(7, 2);
+        let bytes = control.as_bytes();
+
+        assert_eq!(bytes.len(), FrameControl::LENGTH);
+        assert_eq!(FrameControl::try_from(bytes.as_slice()).unwrap(), control);
+        assert_eq!(control.delay_seconds(), 0.1);
+        assert!(FrameControl::try_from(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_animation() {
+        let found = animation(&testing_chunks()).unwrap().unwrap();
+
+        assert_eq!(found.control.num_frames, 2);
+        assert_eq!(found.frames.len(), 2);
+        assert_eq!(found.frames[0].data_indices, [3]);
+        assert!(found.frames[0].is_default_image);
+        assert_eq!(found.frames[1].control_index, 4);
+        assert_eq!(found.frames[1].data_indices, [5, 6]);
+        assert!(animation(&testing_chunks()[..1]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_validate_animation() {
+        assert!(errors(&testing_chunks()).is_empty());
+
+        let mut chunks = testing_chunks();
+        chunks[6] = fdat(5);
+        let diagnostics = errors(&chunks);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].chunk_index, Some(6));
+        assert_eq!(
+            diagnostics[0].message,
+            "fdAT sequence number is 5, expect 3"
+        );
+
+        let mut chunks = testing_chunks();
+        chunks.remove(1);
+        assert_eq!(errors(&chunks)[0].message, "fcTL without acTL");
+
+        let mut chunks = testing_chunks();
+        chunks[4] = chunk("fcTL", fctl(1, 3).as_bytes());
+        assert!(errors(&chunks)[0].message.contains("does not fit"));
+
+        let mut chunks = testing_chunks();
+        chunks.remove(5);
+        chunks.remove(4);
+        assert!(errors(&chunks)
+            .iter()
+            .any(|d| d.message == "acTL has 2 frame(s), found 1 fcTL"));
+    }
+
+    #[test]
+    fn test_frame_tracker() {
+        let mut tracker = FrameTracker::default();
+
+        assert!(!tracker.splits("IDAT"));
+        tracker.push("fcTL");
+        assert!(tracker.splits("IDAT"));
+        assert!(!tracker.splits("tEXt"));
+        tracker.push("fdAT");
+        assert!(tracker.splits("fdAT"));
+        tracker.push("tEXt");
+        assert!(!tracker.splits("fdAT"));
+    }
+}
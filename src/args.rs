@@ -1,49 +1,457 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-#[derive(Parser)]
+use cphoto::{compress::Algorithm, optimize::FilterStrategy, strip, text::TextKind, ChunkPosition};
+
+#[derive(Parser, Clone)]
 pub struct EncodeCmdArgs {
-    /// Input file path.
+    /// Input file path, `-` for stdin, or a directory or glob pattern for batch mode.
     #[arg(short)]
     pub file_path: String,
 
-    /// Chunk type to append.
+    /// Chunk type to append, or a JPEG marker such as APP15 or COM, required in chunk mode.
     #[arg(short)]
-    pub chunk_type: String,
+    pub chunk_type: Option<String>,
 
-    /// Message to append.
-    #[arg(short)]
-    pub message: String,
+    /// How the message is embedded.
+    #[arg(long, value_enum, default_value_t = EmbedMode::Chunk)]
+    pub mode: EmbedMode,
+
+    /// Message to append, read from stdin when neither a message nor a file is given.
+    #[arg(short, conflicts_with = "payload_file_path")]
+    pub message: Option<String>,
+
+    /// File to append instead of a message, `-` for stdin.
+    #[arg(long = "file")]
+    pub payload_file_path: Option<String>,
+
+    /// MIME type recorded for the file, guessed from its extension by default.
+    #[arg(long, requires = "payload_file_path")]
+    pub mime: Option<String>,
 
-    /// Output file path.
+    /// Store the file without its name, MIME type and size.
+    #[arg(long, requires = "payload_file_path")]
+    pub no_envelope: bool,
+
+    /// Output file path, `-` for stdout.
     #[arg(short)]
     pub output_file_path: Option<String>,
+
+    /// Where to place the chunk: before-iend (the default), after-ihdr, before-idat or a chunk
+    /// index between IHDR and IEND.
+    /// Not accepted for JPEG, segments always go after the leading APPn segments.
+    #[arg(short)]
+    pub position: Option<ChunkPosition>,
+
+    /// Encrypt the message with a key derived from this passphrase. Other users can see it in
+    /// the process list, prefer the environment variable or --passphrase-file.
+    #[arg(short = 'k', env = "CPHOTO_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+
+    /// Read the passphrase from the first line of this file.
+    #[arg(long, conflicts_with = "passphrase")]
+    pub passphrase_file: Option<String>,
+
+    /// Split the message into numbered chunks of at most this many bytes.
+    #[arg(short)]
+    pub split_size: Option<usize>,
+
+    /// Compress the payload before embedding it: zlib or zstd.
+    #[arg(long)]
+    pub compress: Option<Algorithm>,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
 }
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 pub struct DecodeCmdArgs {
-    /// Input file path.
+    /// Input file path, `-` for stdin, or a directory or glob pattern for batch mode.
     #[arg(short)]
     pub file_path: String,
 
-    /// Chunk type for decode.
+    /// Chunk type for decode, or the JPEG marker the message is in, required in chunk mode.
     #[arg(short)]
-    pub chunk_type: String,
+    pub chunk_type: Option<String>,
+
+    /// How the message was embedded.
+    #[arg(long, value_enum, default_value_t = EmbedMode::Chunk)]
+    pub mode: EmbedMode,
+
+    /// Passphrase the message was encrypted with. Other users can see it in the process list,
+    /// prefer the environment variable or --passphrase-file.
+    #[arg(short = 'k', env = "CPHOTO_PASSPHRASE", hide_env_values = true)]
+    pub passphrase: Option<String>,
+
+    /// Read the passphrase from the first line of this file.
+    #[arg(long, conflicts_with = "passphrase")]
+    pub passphrase_file: Option<String>,
+
+    /// Write the decoded bytes to this file, `-` for stdout.
+    #[arg(short)]
+    pub output_file_path: Option<String>,
+
+    /// Write the decoded bytes to the file name stored with them.
+    #[arg(short, conflicts_with = "output_file_path")]
+    pub restore: bool,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+}
+
+/// Options for running encode or decode over many files.
+#[derive(Args, Clone)]
+pub struct BatchArgs {
+    /// Write one output per input file into this directory, mirroring the input tree.
+    #[arg(long, conflicts_with = "output_file_path")]
+    pub out_dir: Option<String>,
+
+    /// Walk the subdirectories of a directory input.
+    #[arg(long)]
+    pub recursive: bool,
+
+    /// Files processed at once, one per CPU by default.
+    #[arg(long)]
+    pub jobs: Option<usize>,
+
+    /// Format of the batch summary, json prints one line per file.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
 }
 
 #[derive(Parser)]
 pub struct RemoveCmdArgs {
-    /// Input file path.
+    /// Input file path, `-` for stdin.
     #[arg(short)]
     pub file_path: String,
 
-    /// Chunk type for remove.
+    /// Chunk type for remove, or a JPEG marker such as APP1 or COM.
+    #[arg(short, required_unless_present = "index")]
+    pub chunk_type: Option<String>,
+
+    /// Remove every chunk of the chunk type or segment of the marker instead of the first one.
+    #[arg(short, requires = "chunk_type")]
+    pub all: bool,
+
+    /// Comma separated chunk or segment indices to remove.
+    #[arg(short = 'n', value_delimiter = ',', conflicts_with = "chunk_type")]
+    pub index: Vec<usize>,
+
+    /// Output file path, `-` for stdout.
     #[arg(short)]
-    pub chunk_type: String,
+    pub output_file_path: Option<String>,
+
+    /// Overwrite the input file.
+    #[arg(short, conflicts_with = "output_file_path")]
+    pub in_place: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EmbedMode {
+    /// Store the message in a chunk of its own.
+    Chunk,
+    /// Store the message in the least significant bits of the pixels.
+    Lsb,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
 }
 
 #[derive(Parser)]
 pub struct PrintCmdArgs {
-    /// Input file path.
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser)]
+pub struct ValidateCmdArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser)]
+pub struct RecoverCmdArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Scan for the next valid chunk after a failure instead of stopping.
+    #[arg(long)]
+    pub resync: bool,
+
+    /// Write the recovered chunks to this file as a new PNG, `-` for stdout.
+    #[arg(short)]
+    pub output_file_path: Option<String>,
+
+    /// Write the bytes after IEND to this file, `-` for stdout.
+    #[arg(short)]
+    pub trailing_file_path: Option<String>,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser)]
+pub struct RepairCmdArgs {
+    /// Input file path, `-` for stdin.
     #[arg(short)]
     pub file_path: String,
+
+    /// Output file path, `-` for stdout.
+    #[arg(short)]
+    pub output_file_path: Option<String>,
+
+    /// Overwrite the input file.
+    #[arg(short, conflicts_with = "output_file_path")]
+    pub in_place: bool,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser)]
+pub struct KeygenCmdArgs {
+    /// Secret key file path, the public key is written next to it with a `.pub` extension.
+    /// With `-` the secret key goes to stdout and the public key is only printed to stderr.
+    #[arg(short)]
+    pub output_file_path: String,
+}
+
+#[derive(Parser)]
+pub struct SignCmdArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Secret key file, 32 bytes or 64 hex digits.
+    #[arg(short)]
+    pub key_file_path: String,
+
+    /// Chunk types to cover, all critical chunks by default.
+    #[arg(short, value_delimiter = ',')]
+    pub chunk_types: Vec<String>,
+
+    /// Output file path, `-` for stdout.
+    #[arg(short)]
+    pub output_file_path: Option<String>,
+
+    /// Overwrite the input file.
+    #[arg(short, conflicts_with = "output_file_path")]
+    pub in_place: bool,
+}
+
+#[derive(Parser)]
+pub struct VerifyCmdArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Public key file, 32 bytes or 64 hex digits.
+    #[arg(short)]
+    pub key_file_path: String,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser)]
+pub struct DiffCmdArgs {
+    /// Old file path, `-` for stdin.
+    #[arg(short = 'a')]
+    pub old_file_path: String,
+
+    /// New file path, `-` for stdin.
+    #[arg(short = 'b')]
+    pub new_file_path: String,
+
+    /// Print a patch that turns the old file into the new one instead.
+    #[arg(long, conflicts_with = "format")]
+    pub patch: bool,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser)]
+pub struct PatchCmdArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Patch file made by `diff --patch`, `-` for stdin.
+    #[arg(short)]
+    pub patch_file_path: String,
+
+    /// Output file path, `-` for stdout.
+    #[arg(short)]
+    pub output_file_path: Option<String>,
+
+    /// Overwrite the input file.
+    #[arg(short, conflicts_with = "output_file_path")]
+    pub in_place: bool,
+}
+
+#[derive(Parser)]
+pub struct StripCmdArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Comma separated ancillary chunk types to keep on top of tRNS, gAMA, cHRM, sRGB, iCCP,
+    /// sBIT and cICP.
+    #[arg(long, value_delimiter = ',')]
+    pub keep: Vec<String>,
+
+    /// Largest IDAT chunk to write, the image data is spread evenly over as few as fit.
+    #[arg(long, default_value_t = strip::IDAT_LEN)]
+    pub idat_size: usize,
+
+    /// Output file path, `-` for stdout.
+    #[arg(short)]
+    pub output_file_path: Option<String>,
+
+    /// Overwrite the input file.
+    #[arg(short, conflicts_with = "output_file_path")]
+    pub in_place: bool,
+}
+
+#[derive(Parser)]
+pub struct OptimizeCmdArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Comma separated filter heuristics to try: none, sub, up, average, paeth, min-sum and
+    /// adaptive, all of them by default.
+    #[arg(long, value_delimiter = ',')]
+    pub filters: Vec<FilterStrategy>,
+
+    /// Comma separated deflate levels to try, from 0 to 9.
+    #[arg(long, value_delimiter = ',', default_value = "6,9")]
+    pub levels: Vec<u32>,
+
+    /// Only keep these comma separated ancillary chunk types, `--keep` alone drops them all.
+    /// Every chunk is kept by default.
+    #[arg(long, value_delimiter = ',', num_args = 0..)]
+    pub keep: Option<Vec<String>>,
+
+    /// Largest IDAT chunk to write, the image data is spread evenly over as few as fit.
+    #[arg(long, default_value_t = strip::IDAT_LEN)]
+    pub idat_size: usize,
+
+    /// Output file path, `-` for stdout.
+    #[arg(short)]
+    pub output_file_path: Option<String>,
+
+    /// Overwrite the input file.
+    #[arg(short, conflicts_with = "output_file_path")]
+    pub in_place: bool,
+}
+
+#[derive(Parser)]
+pub struct TextCmdArgs {
+    #[command(subcommand)]
+    pub action: TextAction,
+}
+
+#[derive(Subcommand)]
+pub enum TextAction {
+    /// List the entries of tEXt, zTXt and iTXt chunks
+    List(TextListArgs),
+
+    /// Add an entry
+    Add(TextAddArgs),
+
+    /// Remove every entry with a keyword
+    Remove(TextRemoveArgs),
+}
+
+#[derive(Parser)]
+pub struct TextListArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser)]
+pub struct TextAddArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Keyword of the entry, 1 to 79 Latin-1 characters.
+    #[arg(long)]
+    pub keyword: String,
+
+    /// Text of the entry.
+    #[arg(long)]
+    pub text: String,
+
+    /// Chunk type to store the entry in: tEXt, zTXt or iTXt.
+    #[arg(long = "type", default_value = "tEXt")]
+    pub kind: TextKind,
+
+    /// Compress the text of an iTXt entry.
+    #[arg(long)]
+    pub compress: bool,
+
+    /// Language tag of an iTXt entry, such as en-US.
+    #[arg(long)]
+    pub language: Option<String>,
+
+    /// Keyword translated into the language of an iTXt entry.
+    #[arg(long)]
+    pub translated_keyword: Option<String>,
+
+    /// Where to place the chunk: before-iend, after-ihdr, before-idat or a chunk index between
+    /// IHDR and IEND.
+    #[arg(short, default_value = "before-iend")]
+    pub position: ChunkPosition,
+
+    /// Output file path, `-` for stdout.
+    #[arg(short)]
+    pub output_file_path: Option<String>,
+
+    /// Overwrite the input file.
+    #[arg(short, conflicts_with = "output_file_path")]
+    pub in_place: bool,
+}
+
+#[derive(Parser)]
+pub struct TextRemoveArgs {
+    /// Input file path, `-` for stdin.
+    #[arg(short)]
+    pub file_path: String,
+
+    /// Keyword of the entries to remove.
+    #[arg(long)]
+    pub keyword: String,
+
+    /// Output file path, `-` for stdout.
+    #[arg(short)]
+    pub output_file_path: Option<String>,
+
+    /// Overwrite the input file.
+    #[arg(short, conflicts_with = "output_file_path")]
+    pub in_place: bool,
 }
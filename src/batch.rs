@@ -0,0 +1,331 @@
+use crate::{
+    args::{BatchArgs, DecodeCmdArgs, EncodeCmdArgs, OutputFormat},
+    commands::{write_atomically, Commands},
+};
+use cphoto::Error;
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+fn is_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Whether `path` names many files: a directory, a glob pattern, or anything with `--out-dir`.
+pub fn is_batch(path: &str, batch: &BatchArgs) -> bool {
+    batch.out_dir.is_some() || is_pattern(path) || Path::new(path).is_dir()
+}
+
+/// The files to process, and the directory their output paths are made relative to.
+fn collect_inputs(path: &str, recursive: bool) -> Result<(PathBuf, Vec<PathBuf>), Error> {
+    if Path::new(path).is_dir() {
+        let max_depth = if recursive { usize::MAX } else { 1 };
+        let mut files = Vec::new();
+
+        for entry in WalkDir::new(path).max_depth(max_depth).sort_by_file_name() {
+            let entry = entry.map_err(std::io::Error::from)?;
+            let is_image = entry.path().extension().is_some_and(|ext| {
+                ["png", "jpg", "jpeg"]
+                    .iter()
+                    .any(|image| ext.eq_ignore_ascii_case(image))
+            });
+
+            if entry.file_type().is_file() && is_image {
+                files.push(entry.into_path());
+            }
+        }
+
+        return Ok((path.into(), files));
+    }
+
+    if is_pattern(path) {
+        // Everything before the first component with a wildcard.
+        let base = Path::new(path)
+            .components()
+            .take_while(|c| !is_pattern(&c.as_os_str().to_string_lossy()))
+            .collect();
+        let mut files = Vec::new();
+
+        for entry in glob::glob(path).map_err(|err| Error::InvalidInput(err.to_string()))? {
+            let file = entry.map_err(std::io::Error::from)?;
+
+            if file.is_file() {
+                files.push(file);
+            }
+        }
+
+        return Ok((base, files));
+    }
+
+    let base = Path::new(path)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    Ok((base, vec![path.into()]))
+}
+
+/// Where the output for `input` goes under `out_dir`, keeping its place in the input tree.
+fn mirror(base: &Path, input: &Path, out_dir: &str) -> Result<PathBuf, Error> {
+    let relative = input.strip_prefix(base).unwrap_or(input);
+    let relative: PathBuf = relative
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect();
+    let output = Path::new(out_dir).join(relative);
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    Ok(output)
+}
+
+/// Run `f` over `inputs` on `jobs` threads, keeping the input order.
+fn run<T, F>(inputs: &[PathBuf], jobs: Option<usize>, f: F) -> Result<Vec<Result<T, Error>>, Error>
+where
+    T: Send,
+    F: Fn(&Path) -> Result<T, Error> + Sync,
+{
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.unwrap_or(0))
+        .build()
+        .map_err(std::io::Error::other)?;
+
+    Ok(pool.install(|| inputs.par_iter().map(|input| f(input)).collect()))
+}
+
+/// Print one line per file and a summary, failing if any file failed.
+fn report(
+    inputs: &[PathBuf],
+    results: Vec<Result<PathBuf, Error>>,
+    format: OutputFormat,
+) -> Result<(), Error> {
+    let failed = results.iter().filter(|res| res.is_err()).count();
+    let succeeded = results.len() - failed;
+
+    for (input, res) in inputs.iter().zip(results) {
+        match (format, res) {
+            (OutputFormat::Text, Ok(output)) => {
+                println!("ok     {} -> {}", input.display(), output.display())
+            }
+            (OutputFormat::Text, Err(err)) => println!("FAILED {}: {}", input.display(), err),
+            (OutputFormat::Json, Ok(output)) => println!(
+                "{}",
+                serde_json::json!({
+                    "file": input.display().to_string(),
+                    "output": output.display().to_string(),
+                    "ok": true,
+                })
+            ),
+            (OutputFormat::Json, Err(err)) => println!(
+                "{}",
+                serde_json::json!({
+                    "file": input.display().to_string(),
+                    "ok": false,
+                    "error": err.to_string(),
+                    "exit_code": err.exit_code(),
+                })
+            ),
+        }
+    }
+
+    match format {
+        OutputFormat::Text => println!("{} succeeded, {} failed", succeeded, failed),
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::json!({ "succeeded": succeeded, "failed": failed })
+        ),
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(Error::InvalidData(format!(
+            "{} of {} file(s) failed",
+            failed,
+            inputs.len()
+        ))),
+    }
+}
+
+fn prepare(file_path: &str, batch: &BatchArgs) -> Result<(String, PathBuf, Vec<PathBuf>), Error> {
+    let out_dir = batch.out_dir.clone().ok_or_else(|| {
+        Error::InvalidInput("--out-dir is needed for a directory or glob input".into())
+    })?;
+    let (base, inputs) = collect_inputs(file_path, batch.recursive)?;
+
+    if inputs.is_empty() {
+        return Err(Error::NotFound(format!(
+            "No PNG or JPEG file matches {}",
+            file_path
+        )));
+    }
+
+    Ok((out_dir, base, inputs))
+}
+
+/// Encode the same payload into every input file.
+pub fn encode(args: &EncodeCmdArgs) -> Result<(), Error> {
+    if args.message.is_none() && args.payload_file_path.is_none() {
+        return Err(Error::InvalidInput(
+            "Batch encode needs -m or --file, stdin can only be read once".into(),
+        ));
+    }
+
+    let (out_dir, base, inputs) = prepare(&args.file_path, &args.batch)?;
+    let results = run(&inputs, args.batch.jobs, |input| {
+        let output = mirror(&base, input, &out_dir)?;
+        let mut args = args.clone();
+
+        args.file_path = input.to_string_lossy().into_owned();
+        args.output_file_path = Some(output.to_string_lossy().into_owned());
+        Commands::encode(&args)?;
+
+        Ok(output)
+    })?;
+
+    report(&inputs, results, args.batch.format)
+}
+
+/// Fail every decoded input whose output path another input also decodes to, so none of them
+/// overwrites another.
+fn reject_collisions(
+    decoded: Vec<Result<(PathBuf, Vec<u8>), Error>>,
+) -> Vec<Result<(PathBuf, Vec<u8>), Error>> {
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+
+    for (output, _) in decoded.iter().flatten() {
+        *counts.entry(output.clone()).or_default() += 1;
+    }
+
+    decoded
+        .into_iter()
+        .map(|res| match res {
+            Ok((output, _)) if counts[&output] > 1 => Err(Error::InvalidInput(format!(
+                "{} is the output of {} inputs",
+                output.display(),
+                counts[&output]
+            ))),
+            res => res,
+        })
+        .collect()
+}
+
+/// Decode every input file into its own output file.
+///
+/// Outputs are named after the input with a `.bin` extension, or after the stored file name
+/// with `-r`. Inputs that would decode to the same output all fail.
+pub fn decode(args: &DecodeCmdArgs) -> Result<(), Error> {
+    let (out_dir, base, inputs) = prepare(&args.file_path, &args.batch)?;
+    let decoded = run(&inputs, args.batch.jobs, |input| {
+        let mut args = args.clone();
+
+        args.file_path = input.to_string_lossy().into_owned();
+
+        let (file_name, data) = Commands::decode_payload(&args)?;
+        let output = mirror(&base, input, &out_dir)?;
+        let output = match args.restore {
+            true => output.with_file_name(Commands::restored_name(file_name.as_deref())?),
+            false => output.with_extension("bin"),
+        };
+
+        Ok((output, data))
+    })?;
+    let results = reject_collisions(decoded)
+        .into_iter()
+        .map(|res| {
+            let (output, data) = res?;
+
+            write_atomically(&output.to_string_lossy(), |file| {
+                file.write_all(&data).map_err(Error::from)
+            })?;
+
+            Ok(output)
+        })
+        .collect();
+
+    report(&inputs, results, args.batch.format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp directory, named after the test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cphoto-{}-{}", name, std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn test_collect_inputs() {
+        let dir = temp_dir("collect");
+
+        std::fs::create_dir(dir.join("sub")).unwrap();
+
+        for file in ["a.png", "b.JPG", "c.txt", "sub/d.jpeg"] {
+            std::fs::write(dir.join(file), b"").unwrap();
+        }
+
+        let path = dir.to_string_lossy();
+        let (base, files) = collect_inputs(&path, false).unwrap();
+        assert_eq!(base, dir);
+        assert_eq!(files, [dir.join("a.png"), dir.join("b.JPG")]);
+
+        let (_, files) = collect_inputs(&path, true).unwrap();
+        assert_eq!(
+            files,
+            [dir.join("a.png"), dir.join("b.JPG"), dir.join("sub/d.jpeg")]
+        );
+
+        let (base, files) = collect_inputs(&format!("{}/*/*.jpeg", path), false).unwrap();
+        assert_eq!(base, dir);
+        assert_eq!(files, [dir.join("sub/d.jpeg")]);
+
+        let (base, files) = collect_inputs(&format!("{}/a.png", path), false).unwrap();
+        assert_eq!(base, dir);
+        assert_eq!(files, [dir.join("a.png")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_mirror() {
+        let dir = temp_dir("mirror");
+        let out_dir = dir.join("out");
+        let out = out_dir.to_string_lossy();
+
+        let output = mirror(Path::new("in"), Path::new("in/sub/a.png"), &out).unwrap();
+        assert_eq!(output, out_dir.join("sub/a.png"));
+        assert!(out_dir.join("sub").is_dir());
+
+        // Nothing outside the base escapes the output directory.
+        let output = mirror(Path::new("in"), Path::new("../../etc/b.png"), &out).unwrap();
+        assert_eq!(output, out_dir.join("etc/b.png"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reject_collisions() {
+        let decoded = vec![
+            Ok((PathBuf::from("out/a.bin"), b"a".to_vec())),
+            Ok((PathBuf::from("out/same"), b"b".to_vec())),
+            Err(Error::NotFound("no payload".into())),
+            Ok((PathBuf::from("out/same"), b"c".to_vec())),
+        ];
+        let results = reject_collisions(decoded);
+
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(Error::InvalidInput(_))));
+        assert!(matches!(results[2], Err(Error::NotFound(_))));
+        assert!(matches!(results[3], Err(Error::InvalidInput(_))));
+    }
+}
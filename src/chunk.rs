@@ -1,15 +1,80 @@
-use crate::chunk_type::ChunkType;
+use crate::{chunk_type::ChunkType, error::Error};
 use crc::{Crc, CRC_32_ISO_HDLC};
-use std::io::{Error, ErrorKind};
+use std::str::FromStr;
+
+/// Chunks longer than this are rejected by the PNG spec.
+pub(crate) const MAX_LENGTH: u32 = i32::MAX as u32;
+
+/// Printable prefix of `data`, non-printable bytes are shown as `.`.
+pub(crate) fn preview(data: &[u8], max_len: usize) -> String {
+    let mut preview: String = data
+        .iter()
+        .take(max_len)
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+
+    if data.len() > max_len {
+        preview.push_str("...");
+    }
+
+    preview
+}
 
+/// A chunk whose length and CRC always match its type and data.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Chunk {
-    pub c_length: u32,
-    pub c_type: ChunkType,
-    pub c_data: Vec<u8>,
-    pub c_crc: u32,
+    c_length: u32,
+    c_type: ChunkType,
+    c_data: Vec<u8>,
+    c_crc: u32,
+}
+
+/// Builds a [`Chunk`], the type and an expected CRC are checked by [`ChunkBuilder::build`].
+#[derive(Default)]
+pub struct ChunkBuilder {
+    chunk_type: Option<String>,
+    data: Vec<u8>,
+    crc: Option<u32>,
+}
+
+impl ChunkBuilder {
+    pub fn chunk_type(mut self, chunk_type: &str) -> Self {
+        self.chunk_type = Some(chunk_type.into());
+        self
+    }
+
+    pub fn data(mut self, data: impl Into<Vec<u8>>) -> Self {
+        self.data = data.into();
+        self
+    }
+
+    /// Fail the build unless the CRC of the chunk is `crc`.
+    pub fn crc(mut self, crc: u32) -> Self {
+        self.crc = Some(crc);
+        self
+    }
+
+    pub fn build(self) -> Result<Chunk, Error> {
+        let chunk_type = ChunkType::from_str(
+            self.chunk_type
+                .as_deref()
+                .ok_or_else(|| Error::InvalidInput("Chunk type is required".into()))?,
+        )?;
+
+        match self.crc {
+            Some(crc) => Chunk::from_parts(chunk_type, self.data, crc),
+            None => Ok(Chunk::new(chunk_type, self.data)),
+        }
+    }
 }
 
-fn calc_crc(bytes: &[u8]) -> u32 {
+pub(crate) fn calc_crc(bytes: &[u8]) -> u32 {
     let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
     let mut digest = crc.digest();
 
@@ -23,52 +88,95 @@ impl TryFrom<&[u8]> for Chunk {
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         if value.len() < 12 {
-            return Err(Error::new(
-                ErrorKind::Unsupported,
-                format!("At least 16 bytes, got {} byte(s)", value.len()),
-            ));
+            return Err(Error::Truncated {
+                needed: 12,
+                available: value.len(),
+            });
         }
 
         let c_length = u32::from_be_bytes(value[..4].try_into().unwrap());
 
-        if value.len() != (12 + c_length) as usize {
-            return Err(Error::new(ErrorKind::Unsupported, "Invalid data"));
+        if value.len() != 12 + c_length as usize {
+            return Err(Error::InvalidLength {
+                stored: c_length,
+                actual: value.len() - 12,
+            });
         }
 
         let c_type = ChunkType::try_from(TryInto::<[u8; 4]>::try_into(&value[4..8]).unwrap())?;
-        let c_data = value[8..(value.len() - 4) as usize].to_vec();
+        let c_data = value[8..value.len() - 4].to_vec();
         let c_crc =
             u32::from_be_bytes(TryInto::<[u8; 4]>::try_into(&value[value.len() - 4..]).unwrap());
 
-        if calc_crc(&[&value[4..8], &c_data].concat()) != c_crc {
-            return Err(Error::new(ErrorKind::Unsupported, "CRC check failed"));
-        }
-
-        Ok(Self {
-            c_length,
-            c_type,
-            c_data,
-            c_crc,
-        })
+        Self::from_parts(c_type, c_data, c_crc)
     }
 }
 
 impl std::fmt::Display for Chunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", std::str::from_utf8(&self.c_data).unwrap())
+        write!(f, "{}", String::from_utf8_lossy(&self.c_data))
     }
 }
 
 impl Chunk {
-    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        let c_crc = calc_crc(&[chunk_type.bytes().as_slice(), &data].concat());
+    pub fn builder() -> ChunkBuilder {
+        ChunkBuilder::default()
+    }
 
-        Self {
-            c_length: data.len() as u32,
+    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
+        let mut chunk = Self {
+            c_length: 0,
             c_type: chunk_type,
             c_data: data,
-            c_crc,
+            c_crc: 0,
+        };
+
+        chunk.update();
+
+        chunk
+    }
+
+    /// Recompute the length and CRC after the type or data changed.
+    fn update(&mut self) {
+        self.c_length = self.c_data.len() as u32;
+        self.c_crc = calc_crc(&[self.c_type.bytes().as_slice(), &self.c_data].concat());
+    }
+
+    /// Replace the data, returning the old one.
+    pub fn set_data(&mut self, data: Vec<u8>) -> Vec<u8> {
+        let old = std::mem::replace(&mut self.c_data, data);
+
+        self.update();
+
+        old
+    }
+
+    pub fn append_data(&mut self, data: &[u8]) {
+        self.c_data.extend_from_slice(data);
+        self.update();
+    }
+
+    /// Replace the type, returning the old one.
+    pub fn set_type(&mut self, chunk_type: ChunkType) -> ChunkType {
+        let old = std::mem::replace(&mut self.c_type, chunk_type);
+
+        self.update();
+
+        old
+    }
+
+    /// Build a chunk from its already split parts, checking `crc` against them.
+    pub fn from_parts(chunk_type: ChunkType, data: Vec<u8>, crc: u32) -> Result<Chunk, Error> {
+        let chunk = Self::new(chunk_type, data);
+
+        if chunk.c_crc != crc {
+            return Err(Error::CrcMismatch {
+                stored: crc,
+                computed: chunk.c_crc,
+            });
         }
+
+        Ok(chunk)
     }
 
     pub fn length(&self) -> u32 {
@@ -90,13 +198,15 @@ impl Chunk {
     pub fn data_as_string(&self) -> Result<String, Error> {
         match std::str::from_utf8(&self.c_data) {
             Ok(s) => Ok(s.into()),
-            Err(_) => Err(Error::new(
-                ErrorKind::Unsupported,
-                "Can not convert into string",
-            )),
+            Err(_) => Err(Error::InvalidData("Can not convert into string".into())),
         }
     }
 
+    /// Printable prefix of the data, non-printable bytes are shown as `.`.
+    pub fn data_preview(&self, max_len: usize) -> String {
+        preview(&self.c_data, max_len)
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         [
             u32::to_be_bytes(self.c_length).as_slice(),
@@ -112,7 +222,6 @@ impl Chunk {
 mod tests {
     use super::*;
     use crate::chunk_type::ChunkType;
-    use std::str::FromStr;
 
     fn testing_chunk() -> Chunk {
         let data_length: u32 = 42;
@@ -163,6 +272,12 @@ mod tests {
         assert_eq!(chunk_string, expected_chunk_string);
     }
 
+    #[test]
+    fn test_chunk_display_non_utf8() {
+        let chunk = Chunk::new(ChunkType::from_str("IDAT").unwrap(), vec![b'a', 0xff, b'b']);
+        assert_eq!(chunk.to_string(), "a\u{FFFD}b");
+    }
+
     #[test]
     fn test_chunk_crc() {
         let chunk = testing_chunk();
@@ -217,6 +332,74 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_chunk_from_parts() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data = "This is where your secret message will be!"
+            .as_bytes()
+            .to_vec();
+        let chunk = Chunk::from_parts(chunk_type.clone(), data.clone(), 2882656334).unwrap();
+        assert_eq!(chunk.length(), 42);
+        assert!(Chunk::from_parts(chunk_type, data, 2882656333).is_err());
+    }
+
+    #[test]
+    fn test_chunk_builder() {
+        let chunk = Chunk::builder()
+            .chunk_type("RuSt")
+            .data("This is where your secret message will be!")
+            .crc(2882656334)
+            .build()
+            .unwrap();
+        assert_eq!(chunk.length(), 42);
+
+        let chunk = Chunk::builder().chunk_type("IEND").build().unwrap();
+        assert_eq!(chunk.length(), 0);
+
+        assert!(Chunk::builder().data("no type").build().is_err());
+        assert!(Chunk::builder().chunk_type("Ru1t").build().is_err());
+        assert!(Chunk::builder()
+            .chunk_type("RuSt")
+            .data("data")
+            .crc(0)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_chunk_mutation() {
+        let mut chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"This is".to_vec());
+
+        chunk.append_data(b" where");
+        assert_eq!(
+            chunk.set_data(b"This is where your".to_vec()),
+            b"This is where"
+        );
+        chunk.append_data(b" secret message will be!");
+        assert_eq!(chunk.length(), 42);
+        assert_eq!(chunk.crc(), 2882656334);
+        assert_eq!(chunk, testing_chunk());
+
+        let old = chunk.set_type(ChunkType::from_str("ruSt").unwrap());
+        assert_eq!(old.to_string(), "RuSt");
+        assert_ne!(chunk.crc(), 2882656334);
+        assert!(Chunk::try_from(chunk.as_bytes().as_slice()).is_ok());
+
+        assert_eq!(chunk.set_data(vec![]).len(), 42);
+        assert_eq!(chunk.length(), 0);
+        assert!(Chunk::try_from(chunk.as_bytes().as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_chunk_data_preview() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("RuSt").unwrap(),
+            vec![b'a', 0, b'b', 0xff, b' ', b'c'],
+        );
+        assert_eq!(chunk.data_preview(16), "a.b. c");
+        assert_eq!(chunk.data_preview(3), "a.b...");
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -1,13 +1,14 @@
-use std::{io::Error, str::FromStr};
+use crate::error::Error;
+use std::str::FromStr;
 
 const BIT5_FLAG: u8 = 0x20;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChunkType {
-    pub ancillary_bit: u8,
-    pub private_bit: u8,
-    pub reserved_bit: u8,
-    pub stc_bit: u8,
+    ancillary_bit: u8,
+    private_bit: u8,
+    reserved_bit: u8,
+    stc_bit: u8,
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
@@ -26,10 +27,7 @@ impl TryFrom<[u8; 4]> for ChunkType {
             return Ok(chunk);
         }
 
-        Err(Error::new(
-            std::io::ErrorKind::Unsupported,
-            format!("Con not convert {:?} to ChunkType", value),
-        ))
+        Err(Error::InvalidChunkType(value.to_vec()))
     }
 }
 
@@ -48,12 +46,9 @@ impl FromStr for ChunkType {
                     });
                 }
 
-                Err(Error::new(
-                    std::io::ErrorKind::Unsupported,
-                    format!("Unsupported str: {}", s),
-                ))
+                Err(Error::InvalidChunkType(bytes.to_vec()))
             }
-            Err(err) => Err(Error::new(std::io::ErrorKind::Unsupported, err)),
+            Err(_) => Err(Error::InvalidChunkType(s.as_bytes().to_vec())),
         }
     }
 }
@@ -101,10 +96,7 @@ impl ChunkType {
             && c & BIT5_FLAG == 0
             && [a, b, d]
                 .iter()
-                .all(|x| match (Self::is_uppercase(x), x & BIT5_FLAG == 0) {
-                    (true, true) | (false, false) => true,
-                    _ => false,
-                })
+                .all(|x| Self::is_uppercase(x) == (x & BIT5_FLAG == 0))
     }
 
     pub fn is_critical(&self) -> bool {
@@ -1,19 +1,384 @@
 use std::{
-    io::{Error, ErrorKind},
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
     str::FromStr,
 };
 
 use clap::{Parser, Subcommand};
 
-use crate::{
-    args::{DecodeCmdArgs, EncodeCmdArgs, PrintCmdArgs, RemoveCmdArgs},
-    chunk::Chunk,
-    chunk_type::ChunkType,
-    png::Png,
+use crate::args::{
+    DecodeCmdArgs, DiffCmdArgs, EmbedMode, EncodeCmdArgs, KeygenCmdArgs, OptimizeCmdArgs,
+    OutputFormat, PatchCmdArgs, PrintCmdArgs, RecoverCmdArgs, RemoveCmdArgs, RepairCmdArgs,
+    SignCmdArgs, StripCmdArgs, TextAction, TextCmdArgs, ValidateCmdArgs, VerifyCmdArgs,
 };
+use cphoto::{
+    compress, crypto,
+    diff::{self, ChunkChange, Patch},
+    envelope::Envelope,
+    jpeg::{Jpeg, Marker},
+    lsb,
+    optimize::{self, FilterStrategy},
+    payload::{self, Header},
+    repair,
+    sign::{self, Coverage},
+    stream::{self, PngReader, PngWriter},
+    strip,
+    text::{TextEntry, TextKind},
+    validate::Severity,
+    Chunk, ChunkPosition, ChunkType, Error, Format, Png,
+};
+
+const PREVIEW_LEN: usize = 24;
+/// The path that means stdin when reading and stdout when writing.
+const STDIO: &str = "-";
 
 fn read_from_file(path: &str) -> Result<Vec<u8>, Error> {
-    Ok(std::fs::read(path)?)
+    if path == STDIO {
+        let mut buf = Vec::new();
+        std::io::stdin().read_to_end(&mut buf)?;
+        return Ok(buf);
+    }
+
+    std::fs::read(path).map_err(Error::from)
+}
+
+/// Print a status line, to stderr when stdout carries an output file.
+fn report(to_stderr: bool, message: impl std::fmt::Display) {
+    match to_stderr {
+        true => eprintln!("{}", message),
+        false => println!("{}", message),
+    }
+}
+
+fn output_path(path: &Option<String>, format: Format) -> String {
+    match path {
+        Some(path) => path.clone(),
+        None => format!(
+            "./{:?}.{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap(),
+            format.extension()
+        ),
+    }
+}
+
+/// The passphrase given with `-k` or `CPHOTO_PASSPHRASE`, or the first line of `file`.
+fn passphrase_arg(
+    passphrase: &Option<String>,
+    file: &Option<String>,
+) -> Result<Option<String>, Error> {
+    match (passphrase, file) {
+        (Some(passphrase), _) => Ok(Some(passphrase.clone())),
+        (None, Some(path)) => {
+            let content = std::fs::read_to_string(path)?;
+
+            Ok(Some(content.lines().next().unwrap_or_default().into()))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+fn chunk_type_arg(chunk_type: &Option<String>) -> Result<&str, Error> {
+    chunk_type
+        .as_deref()
+        .ok_or_else(|| Error::InvalidInput("Chunk type (-c) is required in chunk mode".into()))
+}
+
+/// Undo what encode did to the data behind `header`, as the header says, returning it with the
+/// file name stored alongside it if any.
+fn open_payload(
+    header: Header,
+    data: Vec<u8>,
+    passphrase: Option<String>,
+) -> Result<(Option<String>, Vec<u8>), Error> {
+    let data = match (header.encrypted, passphrase) {
+        (true, Some(passphrase)) => crypto::open(&data, &passphrase)?,
+        (true, None) => {
+            return Err(Error::InvalidInput(
+                "The payload is encrypted, pass -k/--passphrase-file".into(),
+            ))
+        }
+        (false, Some(_)) => {
+            return Err(Error::InvalidInput(
+                "The payload is not encrypted, drop -k/--passphrase-file".into(),
+            ))
+        }
+        (false, None) => data,
+    };
+    let data = match header.compression {
+        Some(algorithm) => compress::decompress(&data, algorithm)?,
+        None => data,
+    };
+
+    if header.enveloped {
+        let envelope = Envelope::try_from(data.as_slice())?;
+        Ok((envelope.file_name, envelope.data))
+    } else {
+        Ok((None, data))
+    }
+}
+
+/// Collect the chunks of `chunk_type` and split off their header, reassembling split payloads.
+fn read_chunk_payload(path: &str, chunk_type: &str) -> Result<(Header, Vec<u8>), Error> {
+    let (format, input) = open_input(path)?;
+    let (header, reassembled) = match format {
+        Some(Format::Jpeg) => Jpeg::from_reader(input)?.payload(Marker::from_str(chunk_type)?)?,
+        _ => {
+            let mut reader = PngReader::new(input)?;
+            let mut chunks = Vec::new();
+
+            while let Some(header) = reader.next_header()? {
+                if header.chunk_type.to_string() == chunk_type {
+                    chunks.push(reader.read_chunk()?);
+                }
+            }
+
+            if chunks.is_empty() {
+                return Err(Error::ChunkNotFound(chunk_type.into()));
+            }
+
+            payload::unpack(&chunks.iter().map(Chunk::data).collect::<Vec<&[u8]>>())?
+        }
+    };
+
+    if !reassembled.duplicated.is_empty() {
+        eprintln!("Duplicated piece(s) ignored: {:?}", reassembled.duplicated);
+    }
+
+    Ok((header, reassembled.data))
+}
+
+type Input = BufReader<Box<dyn Read>>;
+
+/// Enough leading bytes to tell the formats apart.
+const SNIFF_LEN: u64 = 8;
+
+/// Open `path` and tell its format from the first bytes, which are left unread.
+fn open_input(path: &str) -> Result<(Option<Format>, Input), Error> {
+    let mut input: Box<dyn Read> = match path {
+        STDIO => Box::new(std::io::stdin()),
+        path => Box::new(File::open(path)?),
+    };
+    let mut head = Vec::new();
+
+    // A pipe may hand over fewer bytes per read than a signature takes.
+    input.by_ref().take(SNIFF_LEN).read_to_end(&mut head)?;
+
+    let format = Format::sniff(&head);
+    let input: Box<dyn Read> = Box::new(std::io::Cursor::new(head).chain(input));
+
+    Ok((format, BufReader::new(input)))
+}
+
+/// Read a PNG for LSB mode, JPEG pixels are compressed lossily and can not carry bits.
+fn read_lsb_png(path: &str) -> Result<Png, Error> {
+    let bytes = read_from_file(path)?;
+
+    if Format::sniff(&bytes) == Some(Format::Jpeg) {
+        return Err(Error::InvalidInput("LSB mode only works on PNG".into()));
+    }
+
+    Png::try_from(bytes.as_slice())
+}
+
+/// Remove chunks while copying the rest from `reader`, returning how many were removed.
+fn remove_chunks<R: Read>(
+    args: &RemoveCmdArgs,
+    mut reader: PngReader<R>,
+    output_file_path: &str,
+) -> Result<usize, Error> {
+    write_atomically(output_file_path, |file| {
+        let mut writer = PngWriter::new(file)?;
+        let mut idx = 0;
+        let mut removed = 0;
+
+        while let Some(header) = reader.next_header()? {
+            let matched = match &args.chunk_type {
+                Some(chunk_type) => {
+                    header.chunk_type.to_string() == *chunk_type && (args.all || removed == 0)
+                }
+                None => args.index.contains(&idx),
+            };
+
+            if matched {
+                reader.skip_chunk()?;
+                removed += 1;
+            } else {
+                reader.copy_chunk(&mut writer)?;
+            }
+
+            idx += 1;
+        }
+
+        if let Some(at) = args.index.iter().find(|&&at| at >= idx) {
+            return Err(Error::IndexOutOfRange {
+                index: *at,
+                len: idx,
+            });
+        }
+
+        if let (Some(chunk_type), 0) = (&args.chunk_type, removed) {
+            return Err(Error::ChunkNotFound(chunk_type.into()));
+        }
+
+        writer.finish()?;
+
+        Ok(removed)
+    })
+}
+
+/// Remove segments of a JPEG, `-c` names a marker such as `APP15` or `COM`.
+fn remove_segments(
+    args: &RemoveCmdArgs,
+    mut jpeg: Jpeg,
+    output_file_path: &str,
+) -> Result<usize, Error> {
+    let removed = match &args.chunk_type {
+        Some(marker) if args.all => jpeg.remove_segments(Marker::from_str(marker)?)?.len(),
+        Some(marker) => jpeg.remove_segment(Marker::from_str(marker)?).map(|_| 1)?,
+        None => jpeg.remove_segments_at(&args.index)?.len(),
+    };
+
+    write_atomically(output_file_path, |file| jpeg.write_to(file).map(|_| ()))?;
+
+    Ok(removed)
+}
+
+/// Write through a temp file in the same directory, then rename over `path`.
+///
+/// `-` writes straight to stdout instead.
+pub(crate) fn write_atomically<T, F>(path: &str, write: F) -> Result<T, Error>
+where
+    F: FnOnce(&mut dyn Write) -> Result<T, Error>,
+{
+    let mut options = File::options();
+
+    options.write(true).create(true).truncate(true);
+
+    write_atomically_with(path, &options, write)
+}
+
+/// Like [`write_atomically`], but only the owner can read the file, for secrets.
+fn write_private<T, F>(path: &str, write: F) -> Result<T, Error>
+where
+    F: FnOnce(&mut dyn Write) -> Result<T, Error>,
+{
+    let mut options = File::options();
+
+    // A fresh temp file, so the mode is not left over from an existing one.
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    write_atomically_with(path, &options, write)
+}
+
+fn write_atomically_with<T, F>(
+    path: &str,
+    options: &std::fs::OpenOptions,
+    write: F,
+) -> Result<T, Error>
+where
+    F: FnOnce(&mut dyn Write) -> Result<T, Error>,
+{
+    if path == STDIO {
+        let mut writer = BufWriter::new(std::io::stdout().lock());
+        let value = write(&mut writer)?;
+
+        writer.flush()?;
+
+        return Ok(value);
+    }
+
+    let path = std::path::Path::new(path);
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::InvalidInput("Invalid file path".into()))?;
+    let tmp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let res = options
+        .open(&tmp_path)
+        .map_err(Error::from)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            let value = write(&mut writer)?;
+
+            writer
+                .into_inner()
+                .map_err(|err| err.into_error())?
+                .sync_all()?;
+
+            std::fs::rename(&tmp_path, path)?;
+
+            Ok(value)
+        });
+
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+
+    res
+}
+
+/// List the segments of a JPEG, the counterpart of the chunk table of a PNG.
+fn print_segments(jpeg: &Jpeg, format: OutputFormat) -> Result<String, Error> {
+    let rows = jpeg
+        .segments()
+        .iter()
+        .zip(jpeg.segment_offsets())
+        .enumerate();
+
+    match format {
+        OutputFormat::Text => {
+            let mut table = format!(
+                "Image: JPEG, {} byte(s) of scan data\n\n{:>5}  {:<6}  {:>10}  {:>10}  PREVIEW",
+                jpeg.scan_data().len(),
+                "INDEX",
+                "MARKER",
+                "OFFSET",
+                "LENGTH"
+            );
+
+            for (idx, (segment, offset)) in rows {
+                table.push_str(&format!(
+                    "\n{:>5}  {:<6}  {:>10}  {:>10}  {}",
+                    idx,
+                    segment.marker(),
+                    offset,
+                    segment.length(),
+                    segment.data_preview(PREVIEW_LEN)
+                ));
+            }
+
+            Ok(table)
+        }
+        OutputFormat::Json => {
+            let segments: Vec<serde_json::Value> = rows
+                .map(|(idx, (segment, offset))| {
+                    serde_json::json!({
+                        "index": idx,
+                        "marker": segment.marker().to_string(),
+                        "offset": offset,
+                        "length": segment.length(),
+                        "preview": segment.data_preview(PREVIEW_LEN),
+                    })
+                })
+                .collect();
+
+            Ok(serde_json::json!({
+                "format": "jpeg",
+                "segments": segments,
+                "scan_length": jpeg.scan_data().len(),
+            })
+            .to_string())
+        }
+    }
 }
 
 #[derive(Parser)]
@@ -39,58 +404,1082 @@ pub enum Cmd {
     /// Remove a chunk
     Remove(RemoveCmdArgs),
 
-    /// Remove a chunk
+    /// List chunks of photo, or segments of a JPEG, FLAGS: Critical, Public, Reserved bit valid, Safe to copy
     Print(PrintCmdArgs),
+
+    /// Check chunk ordering against the PNG spec
+    Validate(ValidateCmdArgs),
+
+    /// List, add or remove tEXt, zTXt and iTXt entries
+    Text(TextCmdArgs),
+
+    /// Salvage the chunks of a truncated or corrupt photo
+    Recover(RecoverCmdArgs),
+
+    /// Fix bad CRCs and lengths, and add a missing IEND
+    Repair(RepairCmdArgs),
+
+    /// Create an Ed25519 key pair for signing
+    Keygen(KeygenCmdArgs),
+
+    /// Sign critical or selected chunks with an Ed25519 key
+    Sign(SignCmdArgs),
+
+    /// Check the signature and list the chunks it covers
+    Verify(VerifyCmdArgs),
+
+    /// Compare the chunks of two photos, or print a patch between them
+    Diff(DiffCmdArgs),
+
+    /// Apply a patch printed by diff
+    Patch(PatchCmdArgs),
+
+    /// Drop metadata chunks and encode the image data the same way every time
+    Strip(StripCmdArgs),
+
+    /// Filter and deflate the image data again, keeping the smallest result
+    Optimize(OptimizeCmdArgs),
 }
 
 impl Commands {
     pub fn encode(args: &EncodeCmdArgs) -> Result<(), Error> {
+        let message_from_stdin = match (&args.message, &args.payload_file_path) {
+            (None, Some(path)) => path == STDIO,
+            (message, _) => message.is_none(),
+        };
+
+        if message_from_stdin && args.file_path == STDIO {
+            return Err(Error::InvalidInput(
+                "The photo is read from stdin, give the message with -m or --file".into(),
+            ));
+        }
+
+        let passphrase = passphrase_arg(&args.passphrase, &args.passphrase_file)?;
+        let header = Header {
+            enveloped: args.message.is_none()
+                && args.payload_file_path.is_some()
+                && !args.no_envelope,
+            split: false,
+            encrypted: passphrase.is_some(),
+            compression: args.compress,
+        };
+        let mut data = match (&args.message, &args.payload_file_path) {
+            (Some(message), _) => message.as_bytes().to_vec(),
+            (None, Some(path)) if args.no_envelope => read_from_file(path)?,
+            (None, Some(path)) => {
+                let file_name = std::path::Path::new(path)
+                    .file_name()
+                    .filter(|_| path != STDIO)
+                    .map(|name| name.to_string_lossy().into_owned());
+                let mime_type = args
+                    .mime
+                    .clone()
+                    .or_else(|| Envelope::guess_mime_type(path));
+
+                Envelope::new(file_name, mime_type, read_from_file(path)?).as_bytes()
+            }
+            (None, None) => {
+                let mut buf = Vec::new();
+                std::io::stdin().read_to_end(&mut buf)?;
+                buf
+            }
+        };
+
+        if let Some(algorithm) = args.compress {
+            let compressed = compress::compress(&data, algorithm)?;
+
+            eprintln!(
+                "Compressed with {}: {} -> {} byte(s), {:.1}% of the original",
+                algorithm,
+                data.len(),
+                compressed.len(),
+                100.0 * compressed.len() as f64 / data.len().max(1) as f64
+            );
+
+            data = compressed;
+        }
+
+        if let Some(passphrase) = passphrase {
+            data = crypto::seal(&data, &passphrase)?;
+        }
+
+        if args.mode == EmbedMode::Lsb {
+            if args.split_size.is_some() {
+                return Err(Error::InvalidInput(
+                    "Splitting is not supported in LSB mode".into(),
+                ));
+            }
+
+            let mut png = read_lsb_png(&args.file_path)?;
+            let framed = header.frame(&data);
+
+            eprintln!(
+                "LSB capacity: {} byte(s), payload with its header: {} byte(s)",
+                lsb::capacity(&png)?,
+                framed.len()
+            );
+
+            lsb::embed(&mut png, &framed)?;
+
+            return write_atomically(&output_path(&args.output_file_path, Format::Png), |file| {
+                png.write_to(file).map(|_| ())
+            });
+        }
+
+        let (format, input) = open_input(&args.file_path)?;
+
+        if format == Some(Format::Jpeg) {
+            if args.position.is_some() {
+                return Err(Error::InvalidInput(
+                    "-p does not apply to JPEG, segments always go after the leading APPn segments"
+                        .into(),
+                ));
+            }
+
+            let marker = Marker::from_str(chunk_type_arg(&args.chunk_type)?)?;
+            let mut jpeg = Jpeg::from_reader(input)?;
+
+            jpeg.hide(marker, header, &data, args.split_size)?;
+
+            return write_atomically(&output_path(&args.output_file_path, Format::Jpeg), |file| {
+                jpeg.write_to(file).map(|_| ())
+            });
+        }
+
+        let chunk_type = ChunkType::from_str(chunk_type_arg(&args.chunk_type)?)?;
+        let chunks: Vec<Chunk> = payload::pack(header, &data, args.split_size)?
+            .into_iter()
+            .map(|part| Chunk::new(chunk_type.clone(), part))
+            .collect();
+
+        let mut reader = PngReader::new(input)?;
+
+        write_atomically(&output_path(&args.output_file_path, Format::Png), |file| {
+            let mut writer = PngWriter::new(file)?;
+
+            stream::insert_chunks(
+                &mut reader,
+                &mut writer,
+                &chunks,
+                args.position.unwrap_or(ChunkPosition::BeforeIend),
+            )?;
+
+            writer.finish().map(|_| ())
+        })
+    }
+
+    /// The decoded bytes, with the file name stored alongside them if any.
+    pub fn decode_payload(args: &DecodeCmdArgs) -> Result<(Option<String>, Vec<u8>), Error> {
+        let (header, data) = match args.mode {
+            EmbedMode::Chunk => {
+                read_chunk_payload(&args.file_path, chunk_type_arg(&args.chunk_type)?)?
+            }
+            EmbedMode::Lsb => {
+                let data = lsb::extract(&read_lsb_png(&args.file_path)?)?;
+                let (header, data) = Header::parse(&data)?;
+
+                (header, data.to_vec())
+            }
+        };
+
+        open_payload(
+            header,
+            data,
+            passphrase_arg(&args.passphrase, &args.passphrase_file)?,
+        )
+    }
+
+    /// The file name to restore decoded bytes to.
+    pub fn restored_name(file_name: Option<&str>) -> Result<String, Error> {
+        match file_name.map(std::path::Path::new) {
+            // Only keep the last component so a stored name can not escape the directory.
+            Some(name) => name
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .ok_or_else(|| Error::InvalidData("Stored file name is empty".into())),
+            None => Err(Error::NotFound(
+                "No file name is stored with the message".into(),
+            )),
+        }
+    }
+
+    pub fn decode(args: &DecodeCmdArgs) -> Result<(), Error> {
+        let (file_name, data) = Self::decode_payload(args)?;
+
+        let output_file_path = match (&args.output_file_path, args.restore) {
+            (Some(path), _) => Some(path.clone()),
+            (None, true) => Some(Self::restored_name(file_name.as_deref())?),
+            (None, false) => None,
+        };
+
+        match (output_file_path, std::str::from_utf8(&data)) {
+            (Some(path), _) => {
+                write_atomically(&path, |file| file.write_all(&data).map_err(Error::from))?
+            }
+            (None, Ok(message)) if file_name.is_none() => {
+                println!("Decode message:\n{}", message)
+            }
+            (None, _) => std::io::stdout().write_all(&data)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(args: &RemoveCmdArgs) -> Result<(), Error> {
+        let (format, input) = open_input(&args.file_path)?;
+        let format = format.unwrap_or(Format::Png);
+        let output_file_path = if args.in_place {
+            args.file_path.clone()
+        } else {
+            output_path(&args.output_file_path, format)
+        };
+
+        let removed = match format {
+            Format::Png => remove_chunks(args, PngReader::new(input)?, &output_file_path)?,
+            Format::Jpeg => remove_segments(args, Jpeg::from_reader(input)?, &output_file_path)?,
+        };
+        let unit = match format {
+            Format::Png => "chunk",
+            Format::Jpeg => "segment",
+        };
+
+        report(
+            output_file_path == STDIO,
+            format!("Removed {} {}(s)", removed, unit),
+        );
+
+        Ok(())
+    }
+
+    pub fn print(args: &PrintCmdArgs) -> Result<String, Error> {
+        let bytes = read_from_file(&args.file_path)?;
+
+        if Format::sniff(&bytes) == Some(Format::Jpeg) {
+            return print_segments(&Jpeg::try_from(bytes.as_slice())?, args.format);
+        }
+
+        let png = Png::try_from(bytes.as_slice())?;
+        let rows = png.chunks().iter().zip(png.chunk_offsets()).enumerate();
+        let ihdr = png.ihdr();
+        let animation = png.animation();
+
+        match args.format {
+            OutputFormat::Text => {
+                let mut table = match &ihdr {
+                    Ok(ihdr) => format!("Image: {}\n\n", ihdr),
+                    Err(err) => format!("Image: invalid IHDR, {}\n\n", err),
+                };
+
+                match &animation {
+                    Ok(Some(animation)) => {
+                        table.push_str(&format!("Animation: {}\n", animation));
+
+                        for (idx, frame) in animation.frames.iter().enumerate() {
+                            table.push_str(&format!("  frame {} {}\n", idx, frame));
+                        }
+
+                        table.push('\n');
+                    }
+                    Ok(None) => (),
+                    Err(err) => table.push_str(&format!("Animation: invalid, {}\n\n", err)),
+                }
+
+                table.push_str(&format!(
+                    "{:>5}  {:<4}  {:>10}  {:>10}  {:<10}  {:<5}  PREVIEW",
+                    "INDEX", "TYPE", "OFFSET", "LENGTH", "CRC", "FLAGS"
+                ));
+
+                for (idx, (chunk, offset)) in rows {
+                    let c_type = chunk.chunk_type();
+                    let flags: String = [
+                        (c_type.is_critical(), 'C'),
+                        (c_type.is_public(), 'P'),
+                        (c_type.is_reserved_bit_valid(), 'R'),
+                        (c_type.is_safe_to_copy(), 'S'),
+                    ]
+                    .iter()
+                    .map(|&(set, flag)| if set { flag } else { '-' })
+                    .collect();
+
+                    table.push_str(&format!(
+                        "\n{:>5}  {:<4}  {:>10}  {:>10}  {:#010x}  {:<5}  {}",
+                        idx,
+                        c_type,
+                        offset,
+                        chunk.length(),
+                        chunk.crc(),
+                        flags,
+                        chunk.data_preview(PREVIEW_LEN)
+                    ));
+                }
+
+                Ok(table)
+            }
+            OutputFormat::Json => {
+                let chunks: Vec<serde_json::Value> = rows
+                    .map(|(idx, (chunk, offset))| {
+                        let c_type = chunk.chunk_type();
+
+                        serde_json::json!({
+                            "index": idx,
+                            "type": c_type.to_string(),
+                            "offset": offset,
+                            "length": chunk.length(),
+                            "crc": chunk.crc(),
+                            "critical": c_type.is_critical(),
+                            "public": c_type.is_public(),
+                            "reserved_bit_valid": c_type.is_reserved_bit_valid(),
+                            "safe_to_copy": c_type.is_safe_to_copy(),
+                            "preview": chunk.data_preview(PREVIEW_LEN),
+                        })
+                    })
+                    .collect();
+
+                let image = match &ihdr {
+                    Ok(ihdr) => serde_json::json!({
+                        "width": ihdr.width,
+                        "height": ihdr.height,
+                        "bit_depth": ihdr.bit_depth,
+                        "color_type": ihdr.color_type.value(),
+                        "compression": ihdr.compression,
+                        "filter": ihdr.filter,
+                        "interlace": ihdr.interlace,
+                    }),
+                    Err(_) => serde_json::Value::Null,
+                };
+                let animation = match &animation {
+                    Ok(Some(animation)) => {
+                        let frames: Vec<serde_json::Value> = animation
+                            .frames
+                            .iter()
+                            .map(|frame| {
+                                let control = &frame.control;
+
+                                serde_json::json!({
+                                    "chunk_index": frame.control_index,
+                                    "sequence_number": control.sequence_number,
+                                    "width": control.width,
+                                    "height": control.height,
+                                    "x_offset": control.x_offset,
+                                    "y_offset": control.y_offset,
+                                    "delay": control.delay_seconds(),
+                                    "dispose_op": control.dispose_op.to_string(),
+                                    "blend_op": control.blend_op.to_string(),
+                                    "data_chunks": frame.data_indices,
+                                    "default_image": frame.is_default_image,
+                                })
+                            })
+                            .collect();
+
+                        serde_json::json!({
+                            "num_frames": animation.control.num_frames,
+                            "num_plays": animation.control.num_plays,
+                            "frames": frames,
+                        })
+                    }
+                    _ => serde_json::Value::Null,
+                };
+
+                Ok(serde_json::json!({
+                    "format": "png",
+                    "image": image,
+                    "animation": animation,
+                    "chunks": chunks,
+                })
+                .to_string())
+            }
+        }
+    }
+
+    pub fn validate(args: &ValidateCmdArgs) -> Result<(), Error> {
+        let png = Png::try_from(read_from_file(&args.file_path)?.as_slice())?;
+        let diagnostics = png.validate();
+
+        match args.format {
+            OutputFormat::Text if diagnostics.is_empty() => println!("No problem found"),
+            OutputFormat::Text => {
+                for diagnostic in &diagnostics {
+                    println!("{}", diagnostic);
+                }
+            }
+            OutputFormat::Json => {
+                let diagnostics: Vec<serde_json::Value> = diagnostics
+                    .iter()
+                    .map(|d| {
+                        serde_json::json!({
+                            "severity": d.severity.to_string(),
+                            "chunk_index": d.chunk_index,
+                            "message": d.message,
+                        })
+                    })
+                    .collect();
+
+                println!("{}", serde_json::json!({ "diagnostics": diagnostics }));
+            }
+        }
+
+        let errors = diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+
+        if errors > 0 {
+            return Err(Error::InvalidData(format!("{} error(s) found", errors)));
+        }
+
+        Ok(())
+    }
+
+    pub fn text(args: &TextCmdArgs) -> Result<(), Error> {
+        match &args.action {
+            TextAction::List(args) => {
+                let png = Png::try_from(read_from_file(&args.file_path)?.as_slice())?;
+                let entries = png.text_entries();
+
+                match args.format {
+                    OutputFormat::Text if entries.is_empty() => println!("No text entry found"),
+                    OutputFormat::Text => {
+                        for (idx, entry) in entries {
+                            match entry {
+                                Ok(entry) => println!("[chunk {}] {}", idx, entry),
+                                Err(err) => println!("[chunk {}] invalid, {}", idx, err),
+                            }
+                        }
+                    }
+                    OutputFormat::Json => {
+                        let entries: Vec<serde_json::Value> = entries
+                            .into_iter()
+                            .map(|(idx, entry)| match entry {
+                                Ok(entry) => serde_json::json!({
+                                    "chunk_index": idx,
+                                    "type": entry.kind.chunk_type(),
+                                    "keyword": entry.keyword,
+                                    "text": entry.text,
+                                    "compressed": entry.compressed,
+                                    "language_tag": entry.language_tag,
+                                    "translated_keyword": entry.translated_keyword,
+                                }),
+                                Err(err) => serde_json::json!({
+                                    "chunk_index": idx,
+                                    "error": err.to_string(),
+                                }),
+                            })
+                            .collect();
+
+                        println!("{}", serde_json::json!({ "entries": entries }));
+                    }
+                }
+
+                Ok(())
+            }
+            TextAction::Add(args) => {
+                let international =
+                    args.compress || args.language.is_some() || args.translated_keyword.is_some();
+
+                if international && args.kind != TextKind::International {
+                    return Err(Error::InvalidInput(
+                        "--compress, --language and --translated-keyword need --type iTXt".into(),
+                    ));
+                }
+
+                let entry = TextEntry {
+                    compressed: args.compress || args.kind == TextKind::Compressed,
+                    language_tag: args.language.clone().unwrap_or_default(),
+                    translated_keyword: args.translated_keyword.clone().unwrap_or_default(),
+                    ..TextEntry::new(args.kind, args.keyword.clone(), args.text.clone())
+                };
+                let chunk = entry.to_chunk()?;
+                let mut png = Png::try_from(read_from_file(&args.file_path)?.as_slice())?;
+
+                png.insert_chunk(chunk, args.position)?;
+
+                let output_file_path = if args.in_place {
+                    args.file_path.clone()
+                } else {
+                    output_path(&args.output_file_path, Format::Png)
+                };
+
+                write_atomically(&output_file_path, |file| png.write_to(file).map(|_| ()))
+            }
+            TextAction::Remove(args) => {
+                let mut png = Png::try_from(read_from_file(&args.file_path)?.as_slice())?;
+                let removed = png.remove_text_entries(&args.keyword)?;
+                let output_file_path = if args.in_place {
+                    args.file_path.clone()
+                } else {
+                    output_path(&args.output_file_path, Format::Png)
+                };
+
+                write_atomically(&output_file_path, |file| png.write_to(file).map(|_| ()))?;
+
+                report(
+                    output_file_path == STDIO,
+                    format!("Removed {} text entry(s)", removed.len()),
+                );
+
+                Ok(())
+            }
+        }
+    }
+
+    pub fn recover(args: &RecoverCmdArgs) -> Result<(), Error> {
+        let recovery = cphoto::recover(&read_from_file(&args.file_path)?, args.resync);
+        let to_stderr = [&args.output_file_path, &args.trailing_file_path]
+            .iter()
+            .any(|path| path.as_deref() == Some(STDIO));
+
+        match args.format {
+            OutputFormat::Text => {
+                report(
+                    to_stderr,
+                    format!(
+                        "Signature: {}",
+                        if recovery.signature_valid {
+                            "valid"
+                        } else {
+                            "invalid"
+                        }
+                    ),
+                );
+                report(
+                    to_stderr,
+                    format!("Recovered {} chunk(s)", recovery.chunks.len()),
+                );
+
+                for (idx, c) in recovery.chunks.iter().enumerate() {
+                    report(
+                        to_stderr,
+                        format!(
+                            "{:>5}  {:<4}  offset {:>10}  length {:>10}",
+                            idx,
+                            c.chunk.chunk_type(),
+                            c.offset,
+                            c.chunk.length()
+                        ),
+                    );
+                }
+
+                for failure in &recovery.failures {
+                    report(to_stderr, format!("Failure at {}", failure));
+                }
+
+                report(
+                    to_stderr,
+                    format!("{} byte(s) after IEND", recovery.trailing.len()),
+                );
+            }
+            OutputFormat::Json => {
+                let chunks: Vec<serde_json::Value> = recovery
+                    .chunks
+                    .iter()
+                    .map(|c| {
+                        serde_json::json!({
+                            "type": c.chunk.chunk_type().to_string(),
+                            "offset": c.offset,
+                            "length": c.chunk.length(),
+                        })
+                    })
+                    .collect();
+                let failures: Vec<serde_json::Value> = recovery
+                    .failures
+                    .iter()
+                    .map(|f| {
+                        serde_json::json!({
+                            "offset": f.offset,
+                            "reason": f.reason.to_string(),
+                            "skipped": f.skipped,
+                        })
+                    })
+                    .collect();
+
+                report(
+                    to_stderr,
+                    serde_json::json!({
+                        "signature_valid": recovery.signature_valid,
+                        "chunks": chunks,
+                        "failures": failures,
+                        "trailing_length": recovery.trailing.len(),
+                    }),
+                );
+            }
+        }
+
+        if let Some(path) = &args.output_file_path {
+            if recovery.chunks.is_empty() {
+                return Err(Error::NotFound("No chunk recovered".into()));
+            }
+
+            let png = recovery.to_png();
+
+            write_atomically(path, |file| png.write_to(file).map(|_| ()))?;
+        }
+
+        if let Some(path) = &args.trailing_file_path {
+            write_atomically(path, |file| {
+                file.write_all(&recovery.trailing).map_err(Error::from)
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn repair(args: &RepairCmdArgs) -> Result<(), Error> {
+        let repaired = repair::repair(&read_from_file(&args.file_path)?)?;
+        let output_file_path = if args.in_place {
+            args.file_path.clone()
+        } else {
+            output_path(&args.output_file_path, Format::Png)
+        };
+
+        write_atomically(&output_file_path, |file| {
+            repaired.png.write_to(file).map(|_| ())
+        })?;
+
+        let to_stderr = output_file_path == STDIO;
+
+        match args.format {
+            OutputFormat::Text if repaired.changes.is_empty() => {
+                report(to_stderr, "Nothing to repair")
+            }
+            OutputFormat::Text => {
+                for change in &repaired.changes {
+                    report(to_stderr, change);
+                }
+            }
+            OutputFormat::Json => {
+                let changes: Vec<serde_json::Value> = repaired
+                    .changes
+                    .iter()
+                    .map(|change| match change {
+                        repair::Change::SignatureFixed => {
+                            serde_json::json!({ "kind": "signature_fixed" })
+                        }
+                        repair::Change::CrcFixed {
+                            index,
+                            chunk_type,
+                            stored,
+                            computed,
+                        } => serde_json::json!({
+                            "kind": "crc_fixed",
+                            "chunk_index": index,
+                            "type": chunk_type,
+                            "stored": stored,
+                            "computed": computed,
+                        }),
+                        repair::Change::LengthFixed {
+                            index,
+                            chunk_type,
+                            stored,
+                            actual,
+                        } => serde_json::json!({
+                            "kind": "length_fixed",
+                            "chunk_index": index,
+                            "type": chunk_type,
+                            "stored": stored,
+                            "actual": actual,
+                        }),
+                        repair::Change::IendAdded { index } => {
+                            serde_json::json!({ "kind": "iend_added", "chunk_index": index })
+                        }
+                        repair::Change::Dropped {
+                            offset,
+                            length,
+                            reason,
+                        } => serde_json::json!({
+                            "kind": "dropped",
+                            "offset": offset,
+                            "length": length,
+                            "reason": reason,
+                        }),
+                    })
+                    .collect();
+
+                report(to_stderr, serde_json::json!({ "changes": changes }));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn keygen(args: &KeygenCmdArgs) -> Result<(), Error> {
+        let secret_key = sign::generate_key();
+        let public_key = sign::format_key(&sign::public_key(&secret_key));
+
+        write_private(&args.output_file_path, |file| {
+            writeln!(file, "{}", sign::format_key(&secret_key)).map_err(Error::from)
+        })?;
+
+        // With the secret key on stdout, the public key is only reported.
+        if args.output_file_path != STDIO {
+            write_atomically(&format!("{}.pub", args.output_file_path), |file| {
+                writeln!(file, "{}", public_key).map_err(Error::from)
+            })?;
+        }
+
+        report(
+            args.output_file_path == STDIO,
+            format!("Public key: {}", public_key),
+        );
+
+        Ok(())
+    }
+
+    pub fn sign(args: &SignCmdArgs) -> Result<(), Error> {
+        let secret_key = sign::parse_key(&read_from_file(&args.key_file_path)?)?;
+        let coverage = match args.chunk_types.as_slice() {
+            [] => Coverage::Critical,
+            types => Coverage::Types(
+                types
+                    .iter()
+                    .map(|t| ChunkType::from_str(t))
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
         let mut png = Png::try_from(read_from_file(&args.file_path)?.as_slice())?;
+        let covered = sign::sign(&mut png, &secret_key, coverage)?;
+        let output_file_path = if args.in_place {
+            args.file_path.clone()
+        } else {
+            output_path(&args.output_file_path, Format::Png)
+        };
 
-        png.append_chunk(Chunk::new(
-            ChunkType::from_str(&args.chunk_type)?,
-            args.message.as_bytes().to_vec(),
-        ));
+        write_atomically(&output_file_path, |file| png.write_to(file).map(|_| ()))?;
 
-        std::fs::write(
-            match &args.output_file_path {
-                Some(path) => path.clone(),
-                None => format!(
-                    "./{:?}.png",
-                    std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                ),
-            },
-            png.as_bytes(),
-        )?;
+        report(
+            output_file_path == STDIO,
+            format!("Signed {} chunk(s)", covered.len()),
+        );
 
         Ok(())
     }
 
-    pub fn decode(args: &DecodeCmdArgs) -> Result<String, Error> {
+    pub fn verify(args: &VerifyCmdArgs) -> Result<(), Error> {
+        let public_key = sign::parse_key(&read_from_file(&args.key_file_path)?)?;
         let png = Png::try_from(read_from_file(&args.file_path)?.as_slice())?;
+        let verified = sign::verify(&png, &public_key)?;
 
-        match png.chunk_by_type(&args.chunk_type) {
-            Some(chunk) => Ok(format!("{}", chunk.data_as_string()?)),
-            None => Err(Error::new(
-                ErrorKind::NotFound,
-                format!("No any chunk type is {}", args.chunk_type),
-            )),
+        match args.format {
+            OutputFormat::Text => {
+                println!(
+                    "Signature is valid, covers {}:",
+                    verified.signature.coverage
+                );
+
+                for idx in verified.covered {
+                    let chunk = &png.chunks()[idx];
+
+                    println!(
+                        "[chunk {}] {} {} byte(s)",
+                        idx,
+                        chunk.chunk_type(),
+                        chunk.length()
+                    );
+                }
+            }
+            OutputFormat::Json => {
+                let covered: Vec<serde_json::Value> = verified
+                    .covered
+                    .iter()
+                    .map(|&idx| {
+                        let chunk = &png.chunks()[idx];
+
+                        serde_json::json!({
+                            "index": idx,
+                            "type": chunk.chunk_type().to_string(),
+                            "length": chunk.length(),
+                        })
+                    })
+                    .collect();
+
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "valid": true,
+                        "public_key": sign::format_key(&verified.signature.public_key),
+                        "coverage": verified.signature.coverage.to_string(),
+                        "covered": covered,
+                    })
+                );
+            }
         }
+
+        Ok(())
     }
 
-    pub fn remove(args: &RemoveCmdArgs) -> Result<(), Error> {
+    pub fn diff(args: &DiffCmdArgs) -> Result<(), Error> {
+        if args.old_file_path == STDIO && args.new_file_path == STDIO {
+            return Err(Error::InvalidInput(
+                "Only one of -a and -b can be read from stdin".into(),
+            ));
+        }
+
+        let old = Png::try_from(read_from_file(&args.old_file_path)?.as_slice())?;
+        let new = Png::try_from(read_from_file(&args.new_file_path)?.as_slice())?;
+
+        if args.patch {
+            print!("{}", Patch::between(&old, &new));
+
+            return Ok(());
+        }
+
+        let changes = diff::diff(&old, &new);
+
+        match args.format {
+            OutputFormat::Text if changes.is_empty() => println!("No differences"),
+            OutputFormat::Text => {
+                for change in &changes {
+                    println!("{}", change);
+
+                    if let ChunkChange::Modified { old, new, .. } = change {
+                        for line in diff::text_diff(old, new).unwrap_or_default() {
+                            println!("    {}", line);
+                        }
+                    }
+                }
+            }
+            OutputFormat::Json => {
+                let chunk_json = |chunk: &Chunk| {
+                    serde_json::json!({
+                        "type": chunk.chunk_type().to_string(),
+                        "length": chunk.length(),
+                        "crc": chunk.crc(),
+                    })
+                };
+                let changes: Vec<serde_json::Value> = changes
+                    .iter()
+                    .map(|change| match change {
+                        ChunkChange::Added { index, chunk } => serde_json::json!({
+                            "kind": "added",
+                            "index": index,
+                            "chunk": chunk_json(chunk),
+                        }),
+                        ChunkChange::Removed { index, chunk } => serde_json::json!({
+                            "kind": "removed",
+                            "index": index,
+                            "chunk": chunk_json(chunk),
+                        }),
+                        ChunkChange::Moved { from, to, chunk } => serde_json::json!({
+                            "kind": "moved",
+                            "from": from,
+                            "to": to,
+                            "chunk": chunk_json(chunk),
+                        }),
+                        ChunkChange::Modified {
+                            from,
+                            to,
+                            moved,
+                            old,
+                            new,
+                        } => serde_json::json!({
+                            "kind": "modified",
+                            "from": from,
+                            "to": to,
+                            "moved": moved,
+                            "old": chunk_json(old),
+                            "new": chunk_json(new),
+                            "length_delta": new.length() as i64 - old.length() as i64,
+                            "text_diff": diff::text_diff(old, new),
+                        }),
+                    })
+                    .collect();
+
+                println!("{}", serde_json::json!({ "changes": changes }));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn patch(args: &PatchCmdArgs) -> Result<(), Error> {
+        if args.file_path == STDIO && args.patch_file_path == STDIO {
+            return Err(Error::InvalidInput(
+                "Only one of -f and -p can be read from stdin".into(),
+            ));
+        }
+
+        let patch = String::from_utf8(read_from_file(&args.patch_file_path)?)
+            .map_err(|err| Error::InvalidData(format!("Patch is not UTF-8: {}", err)))?;
+        let patch = Patch::from_str(&patch)?;
         let mut png = Png::try_from(read_from_file(&args.file_path)?.as_slice())?;
+        let output_file_path = if args.in_place {
+            args.file_path.clone()
+        } else {
+            output_path(&args.output_file_path, Format::Png)
+        };
 
-        png.remove_chunk(&args.chunk_type)?;
+        patch.apply(&mut png)?;
+
+        write_atomically(&output_file_path, |file| png.write_to(file).map(|_| ()))?;
+
+        report(
+            output_file_path == STDIO,
+            format!("Applied {} operation(s)", patch.operations.len()),
+        );
 
         Ok(())
     }
 
-    pub fn print(args: &PrintCmdArgs) -> Result<String, Error> {
+    pub fn strip(args: &StripCmdArgs) -> Result<(), Error> {
+        let keep = args
+            .keep
+            .iter()
+            .map(|t| ChunkType::from_str(t))
+            .collect::<Result<Vec<_>, _>>()?;
         let png = Png::try_from(read_from_file(&args.file_path)?.as_slice())?;
+        let stripped = strip::strip(&png, &keep, args.idat_size)?;
+        let output_file_path = if args.in_place {
+            args.file_path.clone()
+        } else {
+            output_path(&args.output_file_path, Format::Png)
+        };
+
+        write_atomically(&output_file_path, |file| {
+            stripped.png.write_to(file).map(|_| ())
+        })?;
+
+        let to_stderr = output_file_path == STDIO;
+        let removed: Vec<String> = stripped
+            .removed
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+
+        match removed.as_slice() {
+            [] => report(to_stderr, "No chunk removed"),
+            removed => report(
+                to_stderr,
+                format!("Removed {} chunk(s): {}", removed.len(), removed.join(", ")),
+            ),
+        }
+
+        report(
+            to_stderr,
+            format!(
+                "Image data: {} -> {} byte(s) in {} IDAT chunk(s)",
+                png.image_data().len(),
+                stripped.png.image_data().len(),
+                stripped.png.chunks_by_type("IDAT").len()
+            ),
+        );
+
+        Ok(())
+    }
+
+    pub fn optimize(args: &OptimizeCmdArgs) -> Result<(), Error> {
+        let keep = match &args.keep {
+            Some(keep) => Some(
+                keep.iter()
+                    .map(|t| ChunkType::from_str(t))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => None,
+        };
+        let options = optimize::Options {
+            strategies: match args.filters.as_slice() {
+                [] => FilterStrategy::ALL.to_vec(),
+                filters => filters.to_vec(),
+            },
+            levels: args.levels.clone(),
+            keep,
+            idat_len: args.idat_size,
+        };
+        let bytes = read_from_file(&args.file_path)?;
+        let png = Png::try_from(bytes.as_slice())?;
+        let optimized = optimize::optimize(&png, &options)?;
+        let output = optimized.png.as_bytes();
+        let output_file_path = if args.in_place {
+            args.file_path.clone()
+        } else {
+            output_path(&args.output_file_path, Format::Png)
+        };
 
-        Ok(png.to_string())
+        write_atomically(&output_file_path, |file| {
+            file.write_all(&output).map_err(Error::from)
+        })?;
+
+        let to_stderr = output_file_path == STDIO;
+        let (before, after) = optimized.image_data_len;
+
+        match optimized.best {
+            Some((strategy, level)) => report(
+                to_stderr,
+                format!("Best: {} filter, deflate level {}", strategy, level),
+            ),
+            None => report(to_stderr, "Image data is already as small as it gets"),
+        }
+
+        if !optimized.removed.is_empty() {
+            let removed: Vec<String> = optimized
+                .removed
+                .iter()
+                .map(|c| c.chunk_type().to_string())
+                .collect();
+
+            report(
+                to_stderr,
+                format!("Removed {} chunk(s): {}", removed.len(), removed.join(", ")),
+            );
+        }
+
+        report(
+            to_stderr,
+            format!("Image data: {} -> {} byte(s)", before, after),
+        );
+        report(
+            to_stderr,
+            format!(
+                "File: {} -> {} byte(s), saved {} byte(s), {:.1}%",
+                bytes.len(),
+                output.len(),
+                bytes.len() as i64 - output.len() as i64,
+                100.0 * (bytes.len() as f64 - output.len() as f64) / bytes.len() as f64
+            ),
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_payload_encrypted() {
+        let header = Header {
+            encrypted: true,
+            compression: Some(compress::Algorithm::Zstd),
+            ..Header::default()
+        };
+        let data = crypto::seal(
+            &compress::compress(b"secret", compress::Algorithm::Zstd).unwrap(),
+            "hunter2",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            open_payload(header, data.clone(), None),
+            Err(Error::InvalidInput(_))
+        ));
+        assert_eq!(
+            open_payload(header, data, Some("hunter2".into())).unwrap(),
+            (None, b"secret".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_open_payload_not_encrypted() {
+        assert!(matches!(
+            open_payload(
+                Header::default(),
+                b"secret".to_vec(),
+                Some("hunter2".into())
+            ),
+            Err(Error::InvalidInput(_))
+        ));
+        assert_eq!(
+            open_payload(Header::default(), b"secret".to_vec(), None).unwrap(),
+            (None, b"secret".to_vec())
+        );
     }
 }
@@ -0,0 +1,156 @@
+use crate::error::Error;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder};
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+
+/// original size (u64)
+const HEADER_LEN: usize = 8;
+
+/// How a payload is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// zlib deflate, the codec PNG itself uses.
+    Zlib,
+    Zstd,
+}
+
+impl Algorithm {
+    pub(crate) fn id(&self) -> u8 {
+        match self {
+            Self::Zlib => 1,
+            Self::Zstd => 2,
+        }
+    }
+}
+
+impl TryFrom<u8> for Algorithm {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Zlib),
+            2 => Ok(Self::Zstd),
+            _ => Err(Error::Unsupported(format!(
+                "Unsupported compression algorithm: {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "zlib" | "deflate" => Ok(Self::Zlib),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(Error::InvalidInput(format!(
+                "Unknown compression: {}, expect zlib or zstd",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Zlib => write!(f, "zlib"),
+            Self::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+/// Compress `data` behind the original size.
+///
+/// Laid out as `size (u64) | compressed data`, the algorithm is recorded in the payload
+/// [`Header`](crate::payload::Header).
+pub fn compress(data: &[u8], algorithm: Algorithm) -> Result<Vec<u8>, Error> {
+    let compressed = match algorithm {
+        Algorithm::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+
+            encoder.write_all(data)?;
+            encoder.finish()?
+        }
+        Algorithm::Zstd => zstd::encode_all(data, zstd::DEFAULT_COMPRESSION_LEVEL)?,
+    };
+
+    Ok([(data.len() as u64).to_be_bytes().as_slice(), &compressed].concat())
+}
+
+/// Decompress a payload produced by [`compress`] with `algorithm`.
+pub fn decompress(payload: &[u8], algorithm: Algorithm) -> Result<Vec<u8>, Error> {
+    if payload.len() < HEADER_LEN {
+        return Err(Error::InvalidData("Compressed payload is truncated".into()));
+    }
+
+    let size = u64::from_be_bytes(payload[..HEADER_LEN].try_into().unwrap());
+    let compressed = &payload[HEADER_LEN..];
+    let mut data = Vec::new();
+
+    // Read one byte past the recorded size so a payload that inflates further is caught.
+    match algorithm {
+        Algorithm::Zlib => ZlibDecoder::new(compressed)
+            .take(size.saturating_add(1))
+            .read_to_end(&mut data)?,
+        Algorithm::Zstd => zstd::Decoder::new(compressed)?
+            .take(size.saturating_add(1))
+            .read_to_end(&mut data)?,
+    };
+
+    if data.len() as u64 != size {
+        return Err(Error::InvalidData(format!(
+            "Compressed payload expects {} byte(s), got {} byte(s)",
+            size,
+            data.len()
+        )));
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_data() -> Vec<u8> {
+        b"All work and no play makes Jack a dull boy. ".repeat(50)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for algorithm in [Algorithm::Zlib, Algorithm::Zstd] {
+            let payload = compress(&testing_data(), algorithm).unwrap();
+
+            assert!(payload.len() < testing_data().len() / 4);
+            assert_eq!(decompress(&payload, algorithm).unwrap(), testing_data());
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let payload = compress(b"", Algorithm::Zlib).unwrap();
+        assert_eq!(decompress(&payload, Algorithm::Zlib).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let mut payload = compress(&testing_data(), Algorithm::Zstd).unwrap();
+        payload[7] ^= 1;
+
+        assert!(matches!(
+            decompress(&payload, Algorithm::Zstd),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_not_compressed() {
+        assert!(decompress(b"hello", Algorithm::Zlib).is_err());
+        assert!(matches!(Algorithm::try_from(9), Err(Error::Unsupported(_))));
+    }
+}
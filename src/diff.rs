@@ -0,0 +1,754 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::Error,
+    hex::{from_hex, to_hex},
+    png::{ChunkPosition, Png},
+    text::TextEntry,
+};
+use std::str::FromStr;
+
+const PATCH_HEADER: &str = "cphoto patch 1";
+
+/// One difference between the chunks of two files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkChange {
+    /// A chunk only in the new file, at `index`.
+    Added { index: usize, chunk: Chunk },
+    /// A chunk only in the old file, at `index`.
+    Removed { index: usize, chunk: Chunk },
+    /// The same chunk, out of order relative to the others.
+    Moved {
+        from: usize,
+        to: usize,
+        chunk: Chunk,
+    },
+    /// A chunk whose data changed, `moved` if it is also out of order.
+    Modified {
+        from: usize,
+        to: usize,
+        moved: bool,
+        old: Chunk,
+        new: Chunk,
+    },
+}
+
+impl std::fmt::Display for ChunkChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added { index, chunk } => write!(
+                f,
+                "+ [chunk {}] {} {} byte(s), CRC {:#010x}",
+                index,
+                chunk.chunk_type(),
+                chunk.length(),
+                chunk.crc()
+            ),
+            Self::Removed { index, chunk } => write!(
+                f,
+                "- [chunk {}] {} {} byte(s), CRC {:#010x}",
+                index,
+                chunk.chunk_type(),
+                chunk.length(),
+                chunk.crc()
+            ),
+            Self::Moved { from, to, chunk } => {
+                write!(
+                    f,
+                    "~ [chunk {} -> {}] {} moved",
+                    from,
+                    to,
+                    chunk.chunk_type()
+                )
+            }
+            Self::Modified {
+                from,
+                to,
+                moved,
+                old,
+                new,
+            } => write!(
+                f,
+                "M [chunk {} -> {}] {} {} -> {} byte(s) ({:+}), CRC {:#010x} -> {:#010x}{}",
+                from,
+                to,
+                new.chunk_type(),
+                old.length(),
+                new.length(),
+                new.length() as i64 - old.length() as i64,
+                old.crc(),
+                new.crc(),
+                if *moved { ", moved" } else { "" }
+            ),
+        }
+    }
+}
+
+/// Pair up the chunks of two files, identical chunks first, then chunks of the same type in
+/// order. Returns `(old index, new index)` sorted by the new index.
+fn match_chunks(old: &[Chunk], new: &[Chunk]) -> Vec<(usize, usize)> {
+    let mut old_used = vec![false; old.len()];
+    let mut new_match = vec![None; new.len()];
+
+    for exact in [true, false] {
+        for (j, chunk) in new.iter().enumerate() {
+            if new_match[j].is_some() {
+                continue;
+            }
+
+            let same = |other: &Chunk| match exact {
+                true => other == chunk,
+                false => other.chunk_type() == chunk.chunk_type(),
+            };
+
+            if let Some(i) = (0..old.len()).find(|&i| !old_used[i] && same(&old[i])) {
+                old_used[i] = true;
+                new_match[j] = Some(i);
+            }
+        }
+    }
+
+    new_match
+        .into_iter()
+        .enumerate()
+        .filter_map(|(j, i)| i.map(|i| (i, j)))
+        .collect()
+}
+
+/// Mark the longest run of increasing `indices`, the ones that kept their relative order.
+fn in_order(indices: &[usize]) -> Vec<bool> {
+    // tails[k] is the position of the smallest last index of an increasing run of length k + 1.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; indices.len()];
+
+    for (pos, &idx) in indices.iter().enumerate() {
+        let k = tails.partition_point(|&t| indices[t] < idx);
+
+        if k > 0 {
+            prev[pos] = Some(tails[k - 1]);
+        }
+
+        match k == tails.len() {
+            true => tails.push(pos),
+            false => tails[k] = pos,
+        }
+    }
+
+    let mut kept = vec![false; indices.len()];
+    let mut cur = tails.last().copied();
+
+    while let Some(pos) = cur {
+        kept[pos] = true;
+        cur = prev[pos];
+    }
+
+    kept
+}
+
+/// For each new chunk, the old chunk it matches and whether it kept its relative order.
+fn matches(old: &[Chunk], new: &[Chunk]) -> Vec<Option<(usize, bool)>> {
+    let pairs = match_chunks(old, new);
+    let kept = in_order(&pairs.iter().map(|&(i, _)| i).collect::<Vec<_>>());
+    let mut matched = vec![None; new.len()];
+
+    for (&(i, j), kept) in pairs.iter().zip(kept) {
+        matched[j] = Some((i, kept));
+    }
+
+    matched
+}
+
+/// Chunks before `index` of the same type as the chunk at `index`.
+fn occurrence(chunks: &[Chunk], index: usize) -> usize {
+    chunks[..index]
+        .iter()
+        .filter(|c| c.chunk_type() == chunks[index].chunk_type())
+        .count()
+}
+
+/// Everything that changed from `old` to `new`, removals first, then the rest in the order of
+/// the new file.
+pub fn diff(old: &Png, new: &Png) -> Vec<ChunkChange> {
+    let (old, new) = (old.chunks(), new.chunks());
+    let matched = matches(old, new);
+    let mut old_matched = vec![false; old.len()];
+
+    matched
+        .iter()
+        .flatten()
+        .for_each(|&(i, _)| old_matched[i] = true);
+
+    let removed = (0..old.len())
+        .filter(|&i| !old_matched[i])
+        .map(|i| ChunkChange::Removed {
+            index: i,
+            chunk: old[i].clone(),
+        });
+    let changed = matched.iter().enumerate().filter_map(|(j, m)| match *m {
+        None => Some(ChunkChange::Added {
+            index: j,
+            chunk: new[j].clone(),
+        }),
+        Some((i, kept)) if old[i] != new[j] => Some(ChunkChange::Modified {
+            from: i,
+            to: j,
+            moved: !kept,
+            old: old[i].clone(),
+            new: new[j].clone(),
+        }),
+        Some((i, false)) => Some(ChunkChange::Moved {
+            from: i,
+            to: j,
+            chunk: new[j].clone(),
+        }),
+        Some(_) => None,
+    });
+
+    removed.chain(changed).collect()
+}
+
+/// Edits past which [`line_diff`] stops looking for the shortest diff, so texts with little in
+/// common take bounded time and memory.
+const MAX_EDITS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Remove,
+    Insert,
+}
+
+/// The shortest edit script from `old` to `new` by Myers' algorithm, `None` if it takes more
+/// than [`MAX_EDITS`] edits.
+fn shortest_edits(old: &[&str], new: &[&str]) -> Option<Vec<Edit>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m).min(MAX_EDITS as isize);
+    // v[k + offset] is the furthest x reached on diagonal k = x - y.
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // The diagonals -d - 1..=d + 1 of v before each round d, enough to walk back from round d.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut found = None;
+
+    'rounds: for d in 0..=max {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+
+        for k in (-d..=d).step_by(2) {
+            let at = (k + offset) as usize;
+            let mut x = match k == -d || (k != d && v[at - 1] < v[at + 1]) {
+                true => v[at + 1],
+                false => v[at - 1] + 1,
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[at] = x;
+
+            if x >= n && y >= m {
+                found = Some(d);
+                break 'rounds;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+
+    for d in (0..=found?).rev() {
+        let v = &trace[d as usize];
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = match k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            true => k + 1,
+            false => k - 1,
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+
+        if d > 0 {
+            edits.push(if x == prev_x {
+                Edit::Insert
+            } else {
+                Edit::Remove
+            });
+        }
+
+        (x, y) = (prev_x, prev_y);
+    }
+
+    edits.reverse();
+
+    Some(edits)
+}
+
+fn line_diff(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_mid, new_mid) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    // Too far apart for a shortest diff, everything old goes and everything new comes.
+    let edits = shortest_edits(old_mid, new_mid).unwrap_or_else(|| {
+        [
+            vec![Edit::Remove; old_mid.len()],
+            vec![Edit::Insert; new_mid.len()],
+        ]
+        .concat()
+    });
+    let (mut i, mut j) = (0, 0);
+    let mut lines: Vec<String> = old[..prefix].iter().map(|l| format!("  {}", l)).collect();
+
+    for edit in edits {
+        match edit {
+            Edit::Keep => {
+                lines.push(format!("  {}", old_mid[i]));
+                i += 1;
+                j += 1;
+            }
+            Edit::Remove => {
+                lines.push(format!("- {}", old_mid[i]));
+                i += 1;
+            }
+            Edit::Insert => {
+                lines.push(format!("+ {}", new_mid[j]));
+                j += 1;
+            }
+        }
+    }
+
+    lines.extend(old[old.len() - suffix..].iter().map(|l| format!("  {}", l)));
+
+    lines
+}
+
+/// Line diff of two text chunks, prefixed `- `, `+ ` or two spaces, `None` unless both are
+/// text chunks that parse.
+pub fn text_diff(old: &Chunk, new: &Chunk) -> Option<Vec<String>> {
+    let old = TextEntry::try_from(old).ok()?.to_string();
+    let new = TextEntry::try_from(new).ok()?.to_string();
+
+    Some(line_diff(&old, &new))
+}
+
+/// One step of a [`Patch`]. Chunks are found by type and occurrence, so the patch also applies
+/// to files other than the one it was made from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Replace the data of the `occurrence`th chunk of the type.
+    Replace {
+        chunk_type: ChunkType,
+        occurrence: usize,
+        data: Vec<u8>,
+    },
+    /// Remove the `occurrence`th chunk of the type.
+    Remove {
+        chunk_type: ChunkType,
+        occurrence: usize,
+    },
+    /// Insert `chunk` after the `occurrence`th chunk of a type, or first without an anchor.
+    Insert {
+        after: Option<(ChunkType, usize)>,
+        chunk: Chunk,
+    },
+}
+
+/// Operations that turn one file into another.
+///
+/// Written one operation per line after a `cphoto patch 1` header:
+///
+/// ```text
+/// replace <type> <occurrence> <hex data>
+/// remove <type> <occurrence>
+/// insert <anchor type> <anchor occurrence> <type> <hex data>
+/// insert - <type> <hex data>
+/// ```
+///
+/// Empty data is written as `-`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Patch {
+    pub operations: Vec<Operation>,
+}
+
+impl Patch {
+    /// The patch that turns `old` into `new`.
+    ///
+    /// Data changes come first, then removals from the back, then insertions in the order of
+    /// `new`, so occurrences stay valid while the patch is applied. A moved chunk is removed
+    /// and inserted again.
+    pub fn between(old: &Png, new: &Png) -> Patch {
+        let (old, new) = (old.chunks(), new.chunks());
+        let matched = matches(old, new);
+        let mut old_kept = vec![false; old.len()];
+        let mut operations = Vec::new();
+
+        for (j, m) in matched.iter().enumerate() {
+            if let Some((i, true)) = *m {
+                old_kept[i] = true;
+
+                if old[i] != new[j] {
+                    operations.push(Operation::Replace {
+                        chunk_type: old[i].chunk_type().clone(),
+                        occurrence: occurrence(old, i),
+                        data: new[j].data().to_vec(),
+                    });
+                }
+            }
+        }
+
+        for i in (0..old.len()).rev().filter(|&i| !old_kept[i]) {
+            operations.push(Operation::Remove {
+                chunk_type: old[i].chunk_type().clone(),
+                occurrence: occurrence(old, i),
+            });
+        }
+
+        for (j, _) in matched
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| !matches!(m, Some((_, true))))
+        {
+            operations.push(Operation::Insert {
+                after: j
+                    .checked_sub(1)
+                    .map(|prev| (new[prev].chunk_type().clone(), occurrence(new, prev))),
+                chunk: new[j].clone(),
+            });
+        }
+
+        Patch { operations }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Apply every operation to `png` in order.
+    pub fn apply(&self, png: &mut Png) -> Result<(), Error> {
+        for operation in &self.operations {
+            match operation {
+                Operation::Replace {
+                    chunk_type,
+                    occurrence,
+                    data,
+                } => {
+                    let idx = nth_index(png, chunk_type, *occurrence)?;
+
+                    png.chunk_mut(idx).unwrap().set_data(data.clone());
+                }
+                Operation::Remove {
+                    chunk_type,
+                    occurrence,
+                } => {
+                    png.remove_chunks_at(&[nth_index(png, chunk_type, *occurrence)?])?;
+                }
+                Operation::Insert { after, chunk } => {
+                    let idx = match after {
+                        Some((chunk_type, occurrence)) => {
+                            nth_index(png, chunk_type, *occurrence)? + 1
+                        }
+                        None => 0,
+                    };
+
+                    png.insert_chunk(chunk.clone(), ChunkPosition::Index(idx))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn nth_index(png: &Png, chunk_type: &ChunkType, occurrence: usize) -> Result<usize, Error> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.chunk_type() == chunk_type)
+        .nth(occurrence)
+        .map(|(idx, _)| idx)
+        .ok_or_else(|| {
+            Error::NotFound(format!(
+                "No {} chunk number {} to patch",
+                chunk_type, occurrence
+            ))
+        })
+}
+
+fn hex_data(data: &[u8]) -> String {
+    match data.is_empty() {
+        true => "-".into(),
+        false => to_hex(data),
+    }
+}
+
+impl std::fmt::Display for Patch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", PATCH_HEADER)?;
+
+        for operation in &self.operations {
+            match operation {
+                Operation::Replace {
+                    chunk_type,
+                    occurrence,
+                    data,
+                } => writeln!(
+                    f,
+                    "replace {} {} {}",
+                    chunk_type,
+                    occurrence,
+                    hex_data(data)
+                )?,
+                Operation::Remove {
+                    chunk_type,
+                    occurrence,
+                } => writeln!(f, "remove {} {}", chunk_type, occurrence)?,
+                Operation::Insert { after, chunk } => {
+                    match after {
+                        Some((chunk_type, occurrence)) => {
+                            write!(f, "insert {} {}", chunk_type, occurrence)?
+                        }
+                        None => write!(f, "insert -")?,
+                    }
+
+                    writeln!(f, " {} {}", chunk.chunk_type(), hex_data(chunk.data()))?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Patch {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate();
+
+        if lines.next().map(|(_, line)| line.trim()) != Some(PATCH_HEADER) {
+            return Err(Error::InvalidData(format!(
+                "Patch does not start with \"{}\"",
+                PATCH_HEADER
+            )));
+        }
+
+        let mut operations = Vec::new();
+
+        for (line_no, line) in lines {
+            let invalid =
+                || Error::InvalidData(format!("Invalid patch line {}: {}", line_no + 1, line));
+            let chunk_type = |s: &str| ChunkType::from_str(s).map_err(|_| invalid());
+            let number = |s: &str| s.parse::<usize>().map_err(|_| invalid());
+            let data = |s: &str| match s {
+                "-" => Ok(Vec::new()),
+                s => from_hex(s).ok_or_else(invalid),
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            operations.push(match fields.as_slice() {
+                [] => continue,
+                ["replace", t, n, d] => Operation::Replace {
+                    chunk_type: chunk_type(t)?,
+                    occurrence: number(n)?,
+                    data: data(d)?,
+                },
+                ["remove", t, n] => Operation::Remove {
+                    chunk_type: chunk_type(t)?,
+                    occurrence: number(n)?,
+                },
+                ["insert", "-", t, d] => Operation::Insert {
+                    after: None,
+                    chunk: Chunk::new(chunk_type(t)?, data(d)?),
+                },
+                ["insert", at, n, t, d] => Operation::Insert {
+                    after: Some((chunk_type(at)?, number(n)?)),
+                    chunk: Chunk::new(chunk_type(t)?, data(d)?),
+                },
+                _ => return Err(invalid()),
+            });
+        }
+
+        Ok(Patch { operations })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(chunks: &[(&str, &[u8])]) -> Png {
+        Png::from_chunks(
+            chunks
+                .iter()
+                .map(|(t, data)| Chunk::new(ChunkType::from_str(t).unwrap(), data.to_vec()))
+                .collect(),
+        )
+    }
+
+    fn old_png() -> Png {
+        png(&[
+            ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            ("tEXt", b"Comment\0hello"),
+            ("gAMA", &[0, 0, 177, 143]),
+            ("IDAT", &[1, 2, 3, 4]),
+            ("IEND", &[]),
+        ])
+    }
+
+    fn new_png() -> Png {
+        png(&[
+            ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            ("gAMA", &[0, 0, 177, 143]),
+            ("tEXt", b"Comment\0hello\nworld"),
+            ("IDAT", &[1, 2, 3, 4]),
+            ("ruSt", b"hi"),
+            ("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_diff() {
+        let changes = diff(&old_png(), &new_png());
+
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(
+            changes[0],
+            ChunkChange::Moved { from: 2, to: 1, .. }
+        ));
+        assert!(matches!(
+            changes[1],
+            ChunkChange::Modified {
+                from: 1,
+                to: 2,
+                moved: false,
+                ..
+            }
+        ));
+        assert!(matches!(changes[2], ChunkChange::Added { index: 4, .. }));
+        assert!(diff(&old_png(), &old_png()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_removed_and_moved() {
+        let old = old_png();
+        let new = png(&[
+            ("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            ("IDAT", &[1, 2, 3, 4]),
+            ("gAMA", &[0, 0, 177, 143]),
+            ("IEND", &[]),
+        ]);
+        let changes = diff(&old, &new);
+
+        assert_eq!(changes.len(), 2);
+        assert!(matches!(changes[0], ChunkChange::Removed { index: 1, .. }));
+        assert!(matches!(changes[1], ChunkChange::Moved { .. }));
+    }
+
+    #[test]
+    fn test_text_diff() {
+        let old = &old_png().chunks()[1].clone();
+        let new = &new_png().chunks()[2].clone();
+
+        assert_eq!(
+            text_diff(old, new).unwrap(),
+            ["  tEXt Comment: hello", "+ world"]
+        );
+        assert!(text_diff(&old_png().chunks()[3], &new_png().chunks()[3]).is_none());
+    }
+
+    #[test]
+    fn test_line_diff() {
+        assert_eq!(
+            line_diff("a\nb\nc\nd", "a\nc\nx\nd"),
+            ["  a", "- b", "  c", "+ x", "  d"]
+        );
+        assert_eq!(line_diff("", "a"), ["+ a"]);
+        assert_eq!(line_diff("a", ""), ["- a"]);
+        assert!(line_diff("", "").is_empty());
+    }
+
+    #[test]
+    fn test_line_diff_far_apart() {
+        // 10k lines each with nothing in common, too many edits for a shortest diff.
+        let old: Vec<String> = (0..10_000).map(|i| format!("old {}", i)).collect();
+        let new: Vec<String> = (0..10_000).map(|i| format!("new {}", i)).collect();
+        let lines = line_diff(&old.join("\n"), &new.join("\n"));
+
+        assert_eq!(lines.len(), 20_000);
+        assert_eq!(lines[0], "- old 0");
+        assert_eq!(lines[10_000], "+ new 0");
+
+        // Few edits in long texts still give the shortest diff.
+        let mut new = old.clone();
+        new[5_000] = "changed".into();
+        new.insert(9_000, "added".into());
+        let lines = line_diff(&old.join("\n"), &new.join("\n"));
+        let changed: Vec<&String> = lines.iter().filter(|l| !l.starts_with("  ")).collect();
+
+        assert_eq!(changed, ["- old 5000", "+ changed", "+ added"]);
+    }
+
+    #[test]
+    fn test_patch_apply() {
+        let patch = Patch::between(&old_png(), &new_png());
+        let mut png = old_png();
+
+        patch.apply(&mut png).unwrap();
+        assert_eq!(png.as_bytes(), new_png().as_bytes());
+        assert!(Patch::between(&png, &new_png()).is_empty());
+    }
+
+    #[test]
+    fn test_patch_apply_other_file() {
+        let patch = Patch::between(&old_png(), &new_png());
+        // Same layout with other pixels.
+        let mut png = old_png();
+        png.set_chunk_data("IDAT", 0, vec![9, 9]).unwrap();
+
+        patch.apply(&mut png).unwrap();
+        assert_eq!(png.chunks()[3].data(), [9, 9]);
+        assert_eq!(png.chunks()[4].chunk_type().to_string(), "ruSt");
+    }
+
+    #[test]
+    fn test_patch_insert_before_ihdr() {
+        let patch = Patch::from_str("cphoto patch 1\ninsert - ruSt 6869").unwrap();
+        assert!(patch.apply(&mut old_png()).is_err());
+
+        // Without an IHDR the front of the file is still open to the missing one.
+        let mut broken = old_png();
+        broken.remove_chunk("IHDR").unwrap();
+        let patch = Patch::between(&broken, &old_png());
+
+        patch.apply(&mut broken).unwrap();
+        assert_eq!(broken.as_bytes(), old_png().as_bytes());
+    }
+
+    #[test]
+    fn test_patch_round_trip() {
+        let patch = Patch::between(&old_png(), &new_png());
+        let text = patch.to_string();
+
+        assert!(text.starts_with("cphoto patch 1\n"));
+        assert_eq!(Patch::from_str(&text).unwrap(), patch);
+        assert!(Patch::from_str("remove IDAT 0").is_err());
+        assert!(Patch::from_str("cphoto patch 1\nremove IDAT x").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::error::Error;
+
+const VERSION: u8 = 1;
+
+/// A payload together with the name, MIME type and size of the original file.
+///
+/// Laid out as `version | name len (u16) | name | mime len (u16) | mime | size (u64) | data`,
+/// an empty name or MIME type means it is unknown. Whether a payload is an envelope at all is
+/// recorded in its [`Header`](crate::payload::Header).
+#[derive(Debug, PartialEq, Eq)]
+pub struct Envelope {
+    pub file_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+fn read_field<'a>(value: &'a [u8], cursor: &mut usize, len: usize) -> Result<&'a [u8], Error> {
+    match value.get(*cursor..*cursor + len) {
+        Some(field) => {
+            *cursor += len;
+            Ok(field)
+        }
+        None => Err(Error::InvalidData(format!(
+            "Envelope is truncated at byte {}",
+            *cursor
+        ))),
+    }
+}
+
+fn read_string(value: &[u8], cursor: &mut usize) -> Result<Option<String>, Error> {
+    let len = u16::from_be_bytes(read_field(value, cursor, 2)?.try_into().unwrap()) as usize;
+    let bytes = read_field(value, cursor, len)?;
+
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Ok(Some(s.into())),
+        Err(_) => Err(Error::InvalidData(
+            "Envelope field is not valid UTF-8".into(),
+        )),
+    }
+}
+
+fn string_bytes(s: &Option<String>) -> Vec<u8> {
+    let bytes = s.as_deref().unwrap_or_default().as_bytes();
+    let len = bytes.len().min(u16::MAX as usize);
+
+    [(len as u16).to_be_bytes().as_slice(), &bytes[..len]].concat()
+}
+
+impl TryFrom<&[u8]> for Envelope {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let mut cursor = 0;
+        let version = read_field(value, &mut cursor, 1)?[0];
+
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion {
+                what: "envelope",
+                version,
+            });
+        }
+
+        let file_name = read_string(value, &mut cursor)?;
+        let mime_type = read_string(value, &mut cursor)?;
+        let size = u64::from_be_bytes(read_field(value, &mut cursor, 8)?.try_into().unwrap());
+        let data = value[cursor..].to_vec();
+
+        if data.len() as u64 != size {
+            return Err(Error::InvalidData(format!(
+                "Envelope expects {} byte(s) of data, got {} byte(s)",
+                size,
+                data.len()
+            )));
+        }
+
+        Ok(Self {
+            file_name,
+            mime_type,
+            data,
+        })
+    }
+}
+
+impl Envelope {
+    pub fn new(file_name: Option<String>, mime_type: Option<String>, data: Vec<u8>) -> Envelope {
+        Self {
+            file_name,
+            mime_type,
+            data,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            [VERSION].as_slice(),
+            &string_bytes(&self.file_name),
+            &string_bytes(&self.mime_type),
+            &(self.data.len() as u64).to_be_bytes(),
+            &self.data,
+        ]
+        .concat()
+    }
+
+    /// Guess a MIME type from the extension of `path`.
+    pub fn guess_mime_type(path: &str) -> Option<String> {
+        let ext = std::path::Path::new(path)
+            .extension()?
+            .to_str()?
+            .to_ascii_lowercase();
+
+        let mime_type = match ext.as_str() {
+            "txt" => "text/plain",
+            "json" => "application/json",
+            "pdf" => "application/pdf",
+            "zip" => "application/zip",
+            "gz" => "application/gzip",
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "html" | "htm" => "text/html",
+            "csv" => "text/csv",
+            _ => "application/octet-stream",
+        };
+
+        Some(mime_type.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let envelope = Envelope::new(
+            Some("secret.bin".into()),
+            Some("application/octet-stream".into()),
+            vec![0, 1, 2, 255],
+        );
+        let bytes = envelope.as_bytes();
+        assert_eq!(Envelope::try_from(bytes.as_slice()).unwrap(), envelope);
+    }
+
+    #[test]
+    fn test_envelope_without_metadata() {
+        let envelope = Envelope::new(None, None, b"data".to_vec());
+        let actual = Envelope::try_from(envelope.as_bytes().as_slice()).unwrap();
+        assert_eq!(actual.file_name, None);
+        assert_eq!(actual.mime_type, None);
+        assert_eq!(actual.data, b"data");
+    }
+
+    #[test]
+    fn test_envelope_size_mismatch() {
+        let mut bytes = Envelope::new(None, None, b"data".to_vec()).as_bytes();
+        bytes.pop();
+        assert!(Envelope::try_from(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_not_envelope() {
+        assert!(Envelope::try_from(b"".as_slice()).is_err());
+        assert!(matches!(
+            Envelope::try_from(b"plain message".as_slice()),
+            Err(Error::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_guess_mime_type() {
+        assert_eq!(
+            Envelope::guess_mime_type("a/b.TXT").as_deref(),
+            Some("text/plain")
+        );
+        assert_eq!(
+            Envelope::guess_mime_type("a.unknown").as_deref(),
+            Some("application/octet-stream")
+        );
+        assert_eq!(Envelope::guess_mime_type("noext"), None);
+    }
+}
@@ -0,0 +1,257 @@
+use std::io::{self, ErrorKind};
+
+/// Errors of this crate.
+///
+/// Every error maps to a process exit code, see [`Error::exit_code`].
+#[derive(Debug)]
+pub enum Error {
+    /// The file does not start with the PNG signature.
+    InvalidSignature([u8; 8]),
+    /// Bytes that are not a valid chunk type.
+    InvalidChunkType(Vec<u8>),
+    /// The stored CRC does not match the type and data of a chunk.
+    CrcMismatch {
+        stored: u32,
+        computed: u32,
+    },
+    /// The length field of a chunk does not match its data.
+    InvalidLength {
+        stored: u32,
+        actual: usize,
+    },
+    /// Fewer bytes than a chunk needs.
+    Truncated {
+        needed: usize,
+        available: usize,
+    },
+    /// No chunk has the chunk type.
+    ChunkNotFound(String),
+    /// A chunk index past the last chunk.
+    IndexOutOfRange {
+        index: usize,
+        len: usize,
+    },
+    /// Something else asked for is not there.
+    NotFound(String),
+    /// Arguments that can not be used together or are missing.
+    InvalidInput(String),
+    /// Data that does not hold together.
+    InvalidData(String),
+    /// A header version this crate can not read.
+    UnsupportedVersion {
+        /// What carries the version, such as `envelope`.
+        what: &'static str,
+        version: u8,
+    },
+    /// An algorithm or feature this crate can not handle.
+    Unsupported(String),
+    /// Decryption failed, either the passphrase is wrong or the payload was changed.
+    WrongPassphrase,
+    /// A signature made by another key than the one checked against.
+    WrongKey([u8; 32]),
+    /// A signature that does not match the chunks it covers.
+    BadSignature,
+    /// An error inside a chunk of a file.
+    InChunk {
+        chunk_index: usize,
+        /// Byte offset of the chunk from the start of the file.
+        offset: usize,
+        source: Box<Error>,
+    },
+    Io(io::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidSignature(header) => write!(f, "PNG file head: {:?} is invalid", header),
+            Self::InvalidChunkType(bytes) => write!(f, "Invalid chunk type: {:?}", bytes),
+            Self::CrcMismatch { stored, computed } => write!(
+                f,
+                "CRC check failed, stored {:#010x}, computed {:#010x}",
+                stored, computed
+            ),
+            Self::InvalidLength { stored, actual } => write!(
+                f,
+                "Chunk length {} does not match {} byte(s) of data",
+                stored, actual
+            ),
+            Self::Truncated { needed, available } => write!(
+                f,
+                "Expect at least {} bytes, got {} byte(s)",
+                needed, available
+            ),
+            Self::ChunkNotFound(chunk_type) => write!(f, "Not any chunk's type is {}", chunk_type),
+            Self::IndexOutOfRange { index, len } => {
+                write!(f, "Index {} is out of range, only {} chunk(s)", index, len)
+            }
+            Self::NotFound(message)
+            | Self::InvalidInput(message)
+            | Self::InvalidData(message)
+            | Self::Unsupported(message) => write!(f, "{}", message),
+            Self::UnsupportedVersion { what, version } => {
+                write!(f, "Unsupported {} version: {}", what, version)
+            }
+            Self::WrongPassphrase => {
+                write!(f, "Wrong passphrase or the message was tampered with")
+            }
+            Self::WrongKey(public_key) => write!(
+                f,
+                "Signed by another key: {}",
+                crate::hex::to_hex(public_key)
+            ),
+            Self::BadSignature => write!(
+                f,
+                "Signature does not match, the covered chunks were changed"
+            ),
+            Self::InChunk {
+                chunk_index,
+                offset,
+                source,
+            } => write!(f, "Chunk {} at offset {}: {}", chunk_index, offset, source),
+            Self::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InChunk { source, .. } => Some(source.as_ref()),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::Io(err) => err,
+            err => io::Error::new(err.kind(), err),
+        }
+    }
+}
+
+impl Error {
+    /// Attach the chunk index and byte offset the error happened at.
+    pub fn in_chunk(self, chunk_index: usize, offset: usize) -> Error {
+        Self::InChunk {
+            chunk_index,
+            offset,
+            source: Box::new(self),
+        }
+    }
+
+    /// The error without the chunk it happened in.
+    pub fn root(&self) -> &Error {
+        match self {
+            Self::InChunk { source, .. } => source.root(),
+            err => err,
+        }
+    }
+
+    /// Whether the data ended early.
+    pub fn is_truncated(&self) -> bool {
+        match self.root() {
+            Self::Truncated { .. } => true,
+            Self::Io(err) => err.kind() == ErrorKind::UnexpectedEof,
+            _ => false,
+        }
+    }
+
+    /// The closest `std::io::ErrorKind`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidSignature(_)
+            | Self::InvalidChunkType(_)
+            | Self::CrcMismatch { .. }
+            | Self::InvalidLength { .. }
+            | Self::InvalidData(_) => ErrorKind::InvalidData,
+            Self::Truncated { .. } => ErrorKind::UnexpectedEof,
+            Self::ChunkNotFound(_) | Self::NotFound(_) => ErrorKind::NotFound,
+            Self::IndexOutOfRange { .. } | Self::InvalidInput(_) => ErrorKind::InvalidInput,
+            Self::UnsupportedVersion { .. } | Self::Unsupported(_) => ErrorKind::Unsupported,
+            Self::WrongPassphrase | Self::WrongKey(_) | Self::BadSignature => {
+                ErrorKind::PermissionDenied
+            }
+            Self::InChunk { source, .. } => source.kind(),
+            Self::Io(err) => err.kind(),
+        }
+    }
+
+    /// Process exit code: 2 for bad input, 3 when something is not found, 4 for malformed
+    /// data, 5 for a wrong passphrase or missing permission, 1 otherwise.
+    pub fn exit_code(&self) -> u8 {
+        match self.kind() {
+            ErrorKind::InvalidInput => 2,
+            ErrorKind::NotFound => 3,
+            ErrorKind::InvalidData | ErrorKind::UnexpectedEof | ErrorKind::Unsupported => 4,
+            ErrorKind::PermissionDenied => 5,
+            _ => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_chunk() {
+        let err = Error::CrcMismatch {
+            stored: 1,
+            computed: 2,
+        }
+        .in_chunk(3, 100);
+        assert!(matches!(err.root(), Error::CrcMismatch { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Chunk 3 at offset 100: CRC check failed, stored 0x00000001, computed 0x00000002"
+        );
+        assert_eq!(err.exit_code(), 4);
+    }
+
+    #[test]
+    fn test_is_truncated() {
+        let err = Error::from(io::Error::from(ErrorKind::UnexpectedEof)).in_chunk(0, 8);
+        assert!(err.is_truncated());
+        assert!(!Error::ChunkNotFound("IDAT".into()).is_truncated());
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        assert_eq!(Error::InvalidInput("bad".into()).exit_code(), 2);
+        assert_eq!(Error::IndexOutOfRange { index: 3, len: 2 }.exit_code(), 2);
+        assert_eq!(Error::ChunkNotFound("IDAT".into()).exit_code(), 3);
+        assert_eq!(Error::InvalidSignature([0; 8]).exit_code(), 4);
+        assert_eq!(
+            Error::from(io::Error::from(ErrorKind::PermissionDenied)).exit_code(),
+            5
+        );
+        assert_eq!(Error::from(io::Error::other("other")).exit_code(), 1);
+        assert_eq!(
+            Error::UnsupportedVersion {
+                what: "envelope",
+                version: 9
+            }
+            .exit_code(),
+            4
+        );
+        assert_eq!(Error::WrongPassphrase.in_chunk(2, 33).exit_code(), 5);
+        assert_eq!(Error::BadSignature.kind(), ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn test_into_io_error() {
+        let err = io::Error::from(Error::ChunkNotFound("IDAT".into()));
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "Not any chunk's type is IDAT");
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{jpeg::Jpeg, png::Png};
+
+/// The container formats a file can be read as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Jpeg,
+}
+
+impl Format {
+    /// Tell the format from the magic bytes at the start of a file.
+    pub fn sniff(bytes: &[u8]) -> Option<Format> {
+        if bytes.starts_with(&Png::STANDARD_HEADER) {
+            Some(Self::Png)
+        } else if bytes.starts_with(&Jpeg::SIGNATURE) {
+            Some(Self::Jpeg)
+        } else {
+            None
+        }
+    }
+
+    /// The usual file extension, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Png => write!(f, "PNG"),
+            Self::Jpeg => write!(f, "JPEG"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(
+            Format::sniff(&[137, 80, 78, 71, 13, 10, 26, 10, 0]),
+            Some(Format::Png)
+        );
+        assert_eq!(Format::sniff(&[0xFF, 0xD8, 0xFF, 0xE0]), Some(Format::Jpeg));
+        assert_eq!(Format::sniff(&[0xFF, 0xD8]), None);
+        assert_eq!(Format::sniff(b"GIF89a"), None);
+    }
+}
@@ -0,0 +1,419 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, error::Error};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Grayscale),
+            2 => Ok(Self::Rgb),
+            3 => Ok(Self::Indexed),
+            4 => Ok(Self::GrayscaleAlpha),
+            6 => Ok(Self::Rgba),
+            _ => Err(Error::InvalidData(format!("Invalid color type: {}", value))),
+        }
+    }
+}
+
+impl std::fmt::Display for ColorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Grayscale => "grayscale",
+                Self::Rgb => "RGB",
+                Self::Indexed => "indexed",
+                Self::GrayscaleAlpha => "grayscale+alpha",
+                Self::Rgba => "RGBA",
+            }
+        )
+    }
+}
+
+impl ColorType {
+    pub fn value(&self) -> u8 {
+        match self {
+            Self::Grayscale => 0,
+            Self::Rgb => 2,
+            Self::Indexed => 3,
+            Self::GrayscaleAlpha => 4,
+            Self::Rgba => 6,
+        }
+    }
+
+    /// Samples per pixel.
+    pub fn channels(&self) -> usize {
+        match self {
+            Self::Grayscale | Self::Indexed => 1,
+            Self::GrayscaleAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        matches!(self, Self::GrayscaleAlpha | Self::Rgba)
+    }
+
+    /// Bit depths the PNG spec allows for this color type.
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            Self::Grayscale => &[1, 2, 4, 8, 16],
+            Self::Indexed => &[1, 2, 4, 8],
+            Self::Rgb | Self::GrayscaleAlpha | Self::Rgba => &[8, 16],
+        }
+    }
+}
+
+/// The image header, always the first chunk of a PNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression: u8,
+    pub filter: u8,
+    pub interlace: u8,
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != Self::LENGTH {
+            return Err(Error::InvalidData(format!(
+                "IHDR needs {} bytes, got {} byte(s)",
+                Self::LENGTH,
+                value.len()
+            )));
+        }
+
+        let ihdr = Self {
+            width: u32::from_be_bytes(value[0..4].try_into().unwrap()),
+            height: u32::from_be_bytes(value[4..8].try_into().unwrap()),
+            bit_depth: value[8],
+            color_type: ColorType::try_from(value[9])?,
+            compression: value[10],
+            filter: value[11],
+            interlace: value[12],
+        };
+
+        ihdr.validate()?;
+
+        Ok(ihdr)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = Error;
+
+    fn try_from(value: &Chunk) -> Result<Self, Self::Error> {
+        if value.chunk_type().to_string() != "IHDR" {
+            return Err(Error::InvalidData(format!(
+                "Expect IHDR, got {}",
+                value.chunk_type()
+            )));
+        }
+
+        Self::try_from(value.data())
+    }
+}
+
+impl std::fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{}, {}-bit {}, {}",
+            self.width,
+            self.height,
+            self.bit_depth,
+            self.color_type,
+            if self.is_interlaced() {
+                "Adam7 interlaced"
+            } else {
+                "non-interlaced"
+            }
+        )
+    }
+}
+
+impl Ihdr {
+    pub const LENGTH: usize = 13;
+    /// The spec limits both dimensions to 2^31 - 1.
+    const MAX_DIMENSION: u32 = i32::MAX as u32;
+    /// Start column, start row, column step and row step of the Adam7 passes.
+    const ADAM7: [(usize, usize, usize, usize); 7] = [
+        (0, 0, 8, 8),
+        (4, 0, 8, 8),
+        (0, 4, 4, 8),
+        (2, 0, 4, 4),
+        (0, 2, 2, 4),
+        (1, 0, 2, 2),
+        (0, 1, 1, 2),
+    ];
+
+    /// Check the header against the PNG spec.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidData(format!(
+                "Image size {}x{} is empty",
+                self.width, self.height
+            )));
+        }
+
+        if self.width > Self::MAX_DIMENSION || self.height > Self::MAX_DIMENSION {
+            return Err(Error::InvalidData(format!(
+                "Image size {}x{} is too large",
+                self.width, self.height
+            )));
+        }
+
+        if !self
+            .color_type
+            .allowed_bit_depths()
+            .contains(&self.bit_depth)
+        {
+            return Err(Error::InvalidData(format!(
+                "Bit depth {} is not allowed for {} images",
+                self.bit_depth, self.color_type
+            )));
+        }
+
+        if self.compression != 0 {
+            return Err(Error::InvalidData(format!(
+                "Unknown compression method: {}",
+                self.compression
+            )));
+        }
+
+        if self.filter != 0 {
+            return Err(Error::InvalidData(format!(
+                "Unknown filter method: {}",
+                self.filter
+            )));
+        }
+
+        if self.interlace > 1 {
+            return Err(Error::InvalidData(format!(
+                "Unknown interlace method: {}",
+                self.interlace
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_interlaced(&self) -> bool {
+        self.interlace == 1
+    }
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.bit_depth as usize * self.color_type.channels()
+    }
+
+    /// Bytes per complete pixel as used by the scanline filters, at least 1.
+    pub fn filter_bpp(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    /// Bytes of a non-interlaced scanline, without its filter byte.
+    pub fn stride(&self) -> usize {
+        (self.width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Scanline stride and row count of every reduced image in the image data, one for a
+    /// non-interlaced image and up to seven Adam7 passes otherwise, empty passes left out.
+    pub fn passes(&self) -> Vec<(usize, usize)> {
+        let (width, height) = (self.width as usize, self.height as usize);
+
+        if !self.is_interlaced() {
+            return vec![(self.stride(), height)];
+        }
+
+        Self::ADAM7
+            .iter()
+            .map(|&(x0, y0, dx, dy)| {
+                (
+                    width.saturating_sub(x0).div_ceil(dx),
+                    height.saturating_sub(y0).div_ceil(dy),
+                )
+            })
+            .filter(|&(w, h)| w > 0 && h > 0)
+            .map(|(w, h)| ((w * self.bits_per_pixel()).div_ceil(8), h))
+            .collect()
+    }
+
+    /// Bytes of inflated image data the passes take, a filter type byte per row included.
+    pub fn image_data_len(&self) -> u64 {
+        self.passes()
+            .iter()
+            .map(|&(stride, rows)| (stride as u64 + 1).saturating_mul(rows as u64))
+            .fold(0, u64::saturating_add)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            self.width.to_be_bytes().as_slice(),
+            &self.height.to_be_bytes(),
+            &[
+                self.bit_depth,
+                self.color_type.value(),
+                self.compression,
+                self.filter,
+                self.interlace,
+            ],
+        ]
+        .concat()
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), self.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_ihdr() -> Ihdr {
+        Ihdr {
+            width: 50,
+            height: 40,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        }
+    }
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let ihdr = testing_ihdr();
+        let chunk = ihdr.to_chunk();
+        assert_eq!(chunk.length(), 13);
+        assert_eq!(Ihdr::try_from(&chunk).unwrap(), ihdr);
+    }
+
+    #[test]
+    fn test_ihdr_geometry() {
+        let ihdr = testing_ihdr();
+        assert_eq!(ihdr.bits_per_pixel(), 32);
+        assert_eq!(ihdr.filter_bpp(), 4);
+        assert_eq!(ihdr.stride(), 200);
+        assert_eq!(ihdr.to_string(), "50x40, 8-bit RGBA, non-interlaced");
+
+        let ihdr = Ihdr {
+            bit_depth: 1,
+            color_type: ColorType::Grayscale,
+            width: 10,
+            ..testing_ihdr()
+        };
+        assert_eq!(ihdr.filter_bpp(), 1);
+        assert_eq!(ihdr.stride(), 2);
+    }
+
+    #[test]
+    fn test_ihdr_passes() {
+        assert_eq!(testing_ihdr().passes(), [(200, 40)]);
+
+        let ihdr = Ihdr {
+            interlace: 1,
+            ..testing_ihdr()
+        };
+        assert_eq!(
+            ihdr.passes(),
+            [
+                (28, 5),
+                (24, 5),
+                (52, 5),
+                (48, 10),
+                (100, 10),
+                (100, 20),
+                (200, 20)
+            ]
+        );
+
+        let ihdr = Ihdr {
+            width: 1,
+            height: 1,
+            bit_depth: 1,
+            color_type: ColorType::Grayscale,
+            interlace: 1,
+            ..testing_ihdr()
+        };
+        assert_eq!(ihdr.passes(), [(1, 1)]);
+    }
+
+    #[test]
+    fn test_ihdr_invalid_bit_depth() {
+        let ihdr = Ihdr {
+            bit_depth: 4,
+            ..testing_ihdr()
+        };
+        assert!(ihdr.validate().is_err());
+
+        let ihdr = Ihdr {
+            bit_depth: 16,
+            color_type: ColorType::Indexed,
+            ..testing_ihdr()
+        };
+        assert!(ihdr.validate().is_err());
+    }
+
+    #[test]
+    fn test_ihdr_invalid_size() {
+        let ihdr = Ihdr {
+            width: 0,
+            ..testing_ihdr()
+        };
+        assert!(ihdr.validate().is_err());
+
+        let ihdr = Ihdr {
+            height: u32::MAX,
+            ..testing_ihdr()
+        };
+        assert!(ihdr.validate().is_err());
+    }
+
+    #[test]
+    fn test_ihdr_invalid_methods() {
+        for ihdr in [
+            Ihdr {
+                compression: 1,
+                ..testing_ihdr()
+            },
+            Ihdr {
+                filter: 1,
+                ..testing_ihdr()
+            },
+            Ihdr {
+                interlace: 2,
+                ..testing_ihdr()
+            },
+        ] {
+            assert!(ihdr.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_ihdr_invalid_bytes() {
+        assert!(Ihdr::try_from([0u8; 12].as_slice()).is_err());
+
+        let mut bytes = testing_ihdr().as_bytes();
+        bytes[9] = 5;
+        assert!(Ihdr::try_from(bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,588 @@
+use crate::{
+    chunk,
+    error::Error,
+    payload::{self, Header},
+    sequence::Reassembled,
+};
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+
+/// Marks the `APPn` and `COM` segments holding a hidden payload, so they are told apart from
+/// the Exif, JFIF or comment segments sharing the marker.
+pub const PAYLOAD_ID: &[u8] = b"cphoto\0";
+/// The length field counts itself, so a segment holds at most this many data bytes.
+pub const MAX_DATA_LEN: usize = u16::MAX as usize - 2;
+
+/// The second byte of a JPEG marker, the first one is always `0xFF`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker(pub u8);
+
+impl Marker {
+    pub const SOI: Marker = Marker(0xD8);
+    pub const EOI: Marker = Marker(0xD9);
+    pub const SOS: Marker = Marker(0xDA);
+    pub const COM: Marker = Marker(0xFE);
+
+    /// `APPn`, `n` from 0 to 15.
+    pub fn app(n: u8) -> Result<Marker, Error> {
+        match n {
+            0..=15 => Ok(Marker(0xE0 + n)),
+            _ => Err(Error::InvalidInput(format!(
+                "APP{} does not exist, expect APP0 to APP15",
+                n
+            ))),
+        }
+    }
+
+    pub fn is_app(&self) -> bool {
+        (0xE0..=0xEF).contains(&self.0)
+    }
+
+    /// Whether a length and data follow the marker, `TEM`, `RSTn`, `SOI` and `EOI` stand alone.
+    pub fn has_length(&self) -> bool {
+        !matches!(self.0, 0x01 | 0xD0..=0xD9)
+    }
+
+    /// Whether a payload can be hidden in segments of this marker.
+    pub fn can_hide(&self) -> bool {
+        self.is_app() || *self == Self::COM
+    }
+}
+
+impl FromStr for Marker {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_ascii_uppercase();
+
+        (0..=u8::MAX)
+            .map(Marker)
+            .find(|marker| marker.to_string().to_ascii_uppercase() == name)
+            .ok_or_else(|| {
+                Error::InvalidInput(format!(
+                    "Unknown JPEG marker: {}, expect a name like APP15 or COM",
+                    s
+                ))
+            })
+    }
+}
+
+impl std::fmt::Display for Marker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            0x01 => write!(f, "TEM"),
+            0xC4 => write!(f, "DHT"),
+            0xC8 => write!(f, "JPG"),
+            0xCC => write!(f, "DAC"),
+            n @ 0xC0..=0xCF => write!(f, "SOF{}", n - 0xC0),
+            n @ 0xD0..=0xD7 => write!(f, "RST{}", n - 0xD0),
+            0xD8 => write!(f, "SOI"),
+            0xD9 => write!(f, "EOI"),
+            0xDA => write!(f, "SOS"),
+            0xDB => write!(f, "DQT"),
+            0xDC => write!(f, "DNL"),
+            0xDD => write!(f, "DRI"),
+            0xDE => write!(f, "DHP"),
+            0xDF => write!(f, "EXP"),
+            n @ 0xE0..=0xEF => write!(f, "APP{}", n - 0xE0),
+            n @ 0xF0..=0xFD => write!(f, "JPG{}", n - 0xF0),
+            0xFE => write!(f, "COM"),
+            n => write!(f, "0x{:02X}", n),
+        }
+    }
+}
+
+/// A marker and the data after its length field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    marker: Marker,
+    data: Vec<u8>,
+}
+
+impl Segment {
+    pub fn new(marker: Marker, data: Vec<u8>) -> Result<Segment, Error> {
+        if !marker.has_length() && !data.is_empty() {
+            return Err(Error::InvalidInput(format!(
+                "{} stands alone and can not carry data",
+                marker
+            )));
+        }
+
+        if data.len() > MAX_DATA_LEN {
+            return Err(Error::InvalidInput(format!(
+                "{} byte(s) do not fit in a segment, at most {}",
+                data.len(),
+                MAX_DATA_LEN
+            )));
+        }
+
+        Ok(Segment { marker, data })
+    }
+
+    pub fn marker(&self) -> Marker {
+        self.marker
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The stored length field, which counts itself but not the marker.
+    pub fn length(&self) -> u16 {
+        match self.marker.has_length() {
+            true => self.data.len() as u16 + 2,
+            false => 0,
+        }
+    }
+
+    /// Printable prefix of the data, non-printable bytes are shown as `.`.
+    pub fn data_preview(&self, max_len: usize) -> String {
+        chunk::preview(&self.data, max_len)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        match self.marker.has_length() {
+            true => [
+                [0xFF, self.marker.0].as_slice(),
+                &self.length().to_be_bytes(),
+                &self.data,
+            ]
+            .concat(),
+            false => vec![0xFF, self.marker.0],
+        }
+    }
+}
+
+/// A JPEG split into the segments up to and including `SOS`, and everything after them.
+///
+/// The entropy-coded data, later scans and `EOI` are kept as they are, so a JPEG is written
+/// back byte for byte apart from padding between markers.
+#[derive(Debug, Clone)]
+pub struct Jpeg {
+    segments: Vec<Segment>,
+    scan: Vec<u8>,
+}
+
+impl TryFrom<&[u8]> for Jpeg {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if !bytes.starts_with(&Self::SIGNATURE) {
+            return Err(Error::InvalidData(format!(
+                "JPEG file head: {:02X?} is invalid",
+                &bytes[..bytes.len().min(Self::SIGNATURE.len())]
+            )));
+        }
+
+        let mut segments = Vec::new();
+        let mut pos = 2;
+
+        loop {
+            let start = pos;
+
+            match bytes.get(pos) {
+                Some(0xFF) => (),
+                Some(_) => {
+                    return Err(Error::InvalidData(format!(
+                        "Expect a marker at offset {}",
+                        pos
+                    )))
+                }
+                None => return Err(Error::NotFound("No SOS segment found".into())),
+            }
+
+            // Any number of 0xFF bytes may pad the space before a marker.
+            while bytes.get(pos) == Some(&0xFF) {
+                pos += 1;
+            }
+
+            let marker = Marker(*bytes.get(pos).ok_or(Error::Truncated {
+                needed: pos + 1,
+                available: bytes.len(),
+            })?);
+
+            pos += 1;
+
+            if marker == Marker::EOI {
+                return Ok(Jpeg {
+                    segments,
+                    scan: bytes[start..].to_vec(),
+                });
+            }
+
+            if !marker.has_length() {
+                segments.push(Segment::new(marker, Vec::new())?);
+                continue;
+            }
+
+            let length = bytes.get(pos..pos + 2).ok_or(Error::Truncated {
+                needed: pos + 2,
+                available: bytes.len(),
+            })?;
+            let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+
+            if length < 2 {
+                return Err(Error::InvalidData(format!(
+                    "{} segment at offset {} has length {}",
+                    marker, start, length
+                )));
+            }
+
+            let data = bytes.get(pos + 2..pos + length).ok_or(Error::Truncated {
+                needed: pos + length,
+                available: bytes.len(),
+            })?;
+
+            segments.push(Segment::new(marker, data.to_vec())?);
+            pos += length;
+
+            if marker == Marker::SOS {
+                return Ok(Jpeg {
+                    segments,
+                    scan: bytes[pos..].to_vec(),
+                });
+            }
+        }
+    }
+}
+
+impl Jpeg {
+    /// `SOI` followed by the first byte of the next marker.
+    pub const SIGNATURE: [u8; 3] = [0xFF, 0xD8, 0xFF];
+
+    /// Read a whole JPEG from `reader`.
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Jpeg, Error> {
+        let mut bytes = Vec::new();
+
+        reader.read_to_end(&mut bytes)?;
+
+        Self::try_from(bytes.as_slice())
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<W, Error> {
+        writer.write_all(&self.as_bytes())?;
+
+        Ok(writer)
+    }
+
+    /// The segments after `SOI`, up to and including the first `SOS`.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The entropy-coded data and everything after it, including `EOI`.
+    pub fn scan_data(&self) -> &[u8] {
+        &self.scan
+    }
+
+    /// Byte offset of every segment from the start of the file.
+    pub fn segment_offsets(&self) -> Vec<usize> {
+        self.segments
+            .iter()
+            .scan(2, |offset, segment| {
+                let current = *offset;
+                *offset += segment.as_bytes().len();
+                Some(current)
+            })
+            .collect()
+    }
+
+    pub fn segments_by_marker(&self, marker: Marker) -> Vec<&Segment> {
+        self.segments
+            .iter()
+            .filter(|s| s.marker == marker)
+            .collect()
+    }
+
+    /// Hide `payload` behind `header` in segments of `marker` and return their indices.
+    ///
+    /// The segments go after the leading `APPn` segments, so Exif and JFIF stay first. A
+    /// payload too big for one segment, or bigger than `split_size`, is split into pieces.
+    pub fn hide(
+        &mut self,
+        marker: Marker,
+        header: Header,
+        payload: &[u8],
+        split_size: Option<usize>,
+    ) -> Result<Vec<usize>, Error> {
+        if !marker.can_hide() {
+            return Err(Error::InvalidInput(format!(
+                "Can not hide a payload in {}, expect APP0 to APP15 or COM",
+                marker
+            )));
+        }
+
+        let room = MAX_DATA_LEN - PAYLOAD_ID.len();
+        let piece_len = room - payload::PIECE_OVERHEAD;
+        let split_size = match split_size {
+            Some(0) => return Err(Error::InvalidInput("Split size must not be 0".into())),
+            Some(size) => Some(size.min(piece_len)),
+            None if payload.len() > room - payload::HEADER_LEN => Some(piece_len),
+            None => None,
+        };
+        let at = self
+            .segments
+            .iter()
+            .position(|s| !s.marker.is_app())
+            .unwrap_or(self.segments.len());
+        let pieces = payload::pack(header, payload, split_size)?
+            .into_iter()
+            .map(|part| Segment::new(marker, [PAYLOAD_ID, &part].concat()))
+            .collect::<Result<Vec<Segment>, Error>>()?;
+        let indices = (at..at + pieces.len()).collect();
+
+        self.segments.splice(at..at, pieces);
+
+        Ok(indices)
+    }
+
+    /// The payload hidden by [`Jpeg::hide`] in segments of `marker`, with its header.
+    pub fn payload(&self, marker: Marker) -> Result<(Header, Reassembled), Error> {
+        let parts: Vec<&[u8]> = self
+            .segments
+            .iter()
+            .filter(|s| s.marker == marker)
+            .filter_map(|s| s.data.strip_prefix(PAYLOAD_ID))
+            .collect();
+
+        match parts.as_slice() {
+            [] => Err(Error::ChunkNotFound(marker.to_string())),
+            parts => payload::unpack(parts),
+        }
+    }
+
+    /// Remove the first segment of `marker`.
+    pub fn remove_segment(&mut self, marker: Marker) -> Result<Segment, Error> {
+        let idx = self.removable_position_of(marker)?;
+
+        Ok(self.segments.remove(idx))
+    }
+
+    pub fn remove_segments(&mut self, marker: Marker) -> Result<Vec<Segment>, Error> {
+        self.removable_position_of(marker)?;
+
+        let (removed, kept) = std::mem::take(&mut self.segments)
+            .into_iter()
+            .partition(|s| s.marker == marker);
+
+        self.segments = kept;
+
+        Ok(removed)
+    }
+
+    /// Remove the segments at `indices`, `SOS` can not be removed as the scan data needs it.
+    pub fn remove_segments_at(&mut self, indices: &[usize]) -> Result<Vec<Segment>, Error> {
+        let mut indices = indices.to_vec();
+
+        indices.sort_unstable();
+        indices.dedup();
+
+        if let Some(&idx) = indices.iter().find(|&&idx| idx >= self.segments.len()) {
+            return Err(Error::IndexOutOfRange {
+                index: idx,
+                len: self.segments.len(),
+            });
+        }
+
+        if indices
+            .iter()
+            .any(|&idx| self.segments[idx].marker == Marker::SOS)
+        {
+            return Err(Self::sos_error());
+        }
+
+        let mut removed: Vec<Segment> = indices
+            .iter()
+            .rev()
+            .map(|&idx| self.segments.remove(idx))
+            .collect();
+
+        removed.reverse();
+
+        Ok(removed)
+    }
+
+    fn removable_position_of(&self, marker: Marker) -> Result<usize, Error> {
+        if marker == Marker::SOS {
+            return Err(Self::sos_error());
+        }
+
+        self.segments
+            .iter()
+            .position(|s| s.marker == marker)
+            .ok_or_else(|| Error::ChunkNotFound(marker.to_string()))
+    }
+
+    fn sos_error() -> Error {
+        Error::InvalidInput("SOS can not be removed, the scan data needs it".into())
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0xFF, Marker::SOI.0];
+
+        for segment in &self.segments {
+            bytes.extend(segment.as_bytes());
+        }
+
+        bytes.extend(&self.scan);
+
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_jpeg() -> Vec<u8> {
+        [
+            [0xFF, 0xD8].as_slice(),
+            // APP0 JFIF
+            &[0xFF, 0xE0, 0x00, 0x10],
+            b"JFIF\0",
+            &[1, 1, 0, 0, 1, 0, 1, 0, 0],
+            // DQT with a dummy table
+            &[0xFF, 0xDB, 0x00, 0x04, 0x00, 0x01],
+            // COM
+            &[0xFF, 0xFE, 0x00, 0x07],
+            b"hello",
+            // SOS, scan data with a stuffed byte and a restart marker, EOI
+            &[0xFF, 0xDA, 0x00, 0x04, 0x01, 0x00],
+            &[0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56],
+            &[0xFF, 0xD9],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_parse() {
+        let jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        let markers: Vec<String> = jpeg
+            .segments()
+            .iter()
+            .map(|s| s.marker().to_string())
+            .collect();
+
+        assert_eq!(markers, ["APP0", "DQT", "COM", "SOS"]);
+        assert_eq!(jpeg.segments()[2].data(), b"hello");
+        assert_eq!(jpeg.segment_offsets(), [2, 20, 26, 35]);
+        assert_eq!(
+            jpeg.scan_data(),
+            [0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56, 0xFF, 0xD9]
+        );
+        assert_eq!(jpeg.as_bytes(), testing_jpeg());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let bytes = testing_jpeg();
+
+        assert!(Jpeg::try_from(&bytes[1..]).is_err());
+        assert!(Jpeg::try_from(&bytes[..30]).unwrap_err().is_truncated());
+        assert!(matches!(
+            Jpeg::try_from(&bytes[..20]).unwrap_err(),
+            Error::NotFound(_)
+        ));
+    }
+
+    #[test]
+    fn test_marker_names() {
+        for (name, marker) in [
+            ("APP0", Marker(0xE0)),
+            ("app15", Marker(0xEF)),
+            ("COM", Marker::COM),
+            ("SOF2", Marker(0xC2)),
+            ("DHT", Marker(0xC4)),
+            ("RST7", Marker(0xD7)),
+        ] {
+            assert_eq!(Marker::from_str(name).unwrap(), marker);
+        }
+
+        assert_eq!(Marker::app(1).unwrap().to_string(), "APP1");
+        assert!(Marker::app(16).is_err());
+        assert!(Marker::from_str("APP16").is_err());
+        assert!(!Marker(0xD0).has_length());
+        assert!(Marker::SOS.has_length());
+    }
+
+    #[test]
+    fn test_hide_and_payload() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        let marker = Marker::app(15).unwrap();
+        let payload = vec![7u8; 150_000];
+
+        let indices = jpeg
+            .hide(marker, Header::default(), &payload, None)
+            .unwrap();
+
+        assert_eq!(indices, [1, 2, 3]);
+        assert_eq!(jpeg.segments()[0].marker(), Marker(0xE0));
+        assert_eq!(jpeg.segments()[4].marker().to_string(), "DQT");
+
+        let jpeg = Jpeg::try_from(jpeg.as_bytes().as_slice()).unwrap();
+
+        assert_eq!(jpeg.payload(marker).unwrap().1.data, payload);
+        assert!(matches!(
+            jpeg.payload(Marker::COM).unwrap_err(),
+            Error::ChunkNotFound(_)
+        ));
+    }
+
+    #[test]
+    fn test_hide_split_and_com() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+
+        let header = Header::default();
+
+        assert_eq!(
+            jpeg.hide(Marker::COM, header, b"secret", Some(4)).unwrap(),
+            [1, 2]
+        );
+        assert_eq!(jpeg.payload(Marker::COM).unwrap().1.data, b"secret");
+        assert_eq!(jpeg.segments_by_marker(Marker::COM).len(), 3);
+
+        assert!(jpeg.hide(Marker::SOS, header, b"secret", None).is_err());
+        assert!(jpeg.hide(Marker::COM, header, b"secret", Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_payload_checks_pieces() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+        let header = Header::default();
+
+        jpeg.hide(Marker::COM, header, b"first", Some(2)).unwrap();
+        // The segments of the second payload go in front of the first one.
+        jpeg.hide(Marker::COM, header, b"second", Some(2)).unwrap();
+        assert_eq!(jpeg.payload(Marker::COM).unwrap().1.data, b"second");
+
+        jpeg.remove_segments_at(&[2]).unwrap();
+        assert!(matches!(jpeg.payload(Marker::COM), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut jpeg = Jpeg::try_from(testing_jpeg().as_slice()).unwrap();
+
+        jpeg.hide(Marker::COM, Header::default(), b"secret", Some(2))
+            .unwrap();
+
+        assert!(jpeg
+            .remove_segment(Marker::COM)
+            .unwrap()
+            .data()
+            .starts_with(PAYLOAD_ID));
+        assert_eq!(jpeg.remove_segments(Marker::COM).unwrap().len(), 3);
+        assert!(jpeg.remove_segment(Marker::COM).is_err());
+        assert!(jpeg.remove_segment(Marker::SOS).is_err());
+        assert!(jpeg.remove_segments_at(&[2]).is_err());
+        assert!(jpeg.remove_segments_at(&[9]).is_err());
+
+        let removed = jpeg.remove_segments_at(&[1, 0]).unwrap();
+
+        assert_eq!(removed[0].marker(), Marker(0xE0));
+        assert_eq!(jpeg.segments().len(), 1);
+        assert!(Jpeg::try_from(jpeg.as_bytes().as_slice()).is_ok());
+    }
+}
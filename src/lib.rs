@@ -0,0 +1,39 @@
+//! Read, edit and write PNG files chunk by chunk, and JPEG files segment by segment, and hide
+//! payloads in them.
+//!
+//! [`Png`], [`Chunk`] and [`ChunkType`] are the core of the API, the public modules build on
+//! them, one per feature. Helpers such as filtering scanlines or hex encoding stay private.
+
+pub mod apng;
+mod chunk;
+mod chunk_type;
+pub mod compress;
+pub mod crypto;
+pub mod diff;
+pub mod envelope;
+mod error;
+mod format;
+mod hex;
+pub mod ihdr;
+pub mod jpeg;
+pub mod lsb;
+pub mod optimize;
+pub mod payload;
+mod png;
+mod recover;
+pub mod repair;
+mod scanline;
+pub mod sequence;
+pub mod sign;
+pub mod stream;
+pub mod strip;
+pub mod text;
+pub mod validate;
+
+pub use chunk::{Chunk, ChunkBuilder};
+pub use chunk_type::ChunkType;
+pub use error::Error;
+pub use format::Format;
+pub use jpeg::Jpeg;
+pub use png::{ChunkPosition, Png, PngBuilder};
+pub use recover::{recover, Failure, FailureReason, RecoveredChunk, Recovery};
@@ -0,0 +1,275 @@
+use crate::{error::Error, ihdr::ColorType, png::Png, scanline};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::io::{Read, Write};
+
+const MAGIC: [u8; 4] = *b"CPLS";
+/// magic + payload length (u32)
+const HEADER_LEN: usize = 4 + 4;
+const IDAT_LEN: usize = 64 * 1024;
+
+/// Pixel layout of an image whose sample LSBs can carry a payload.
+struct Layout {
+    width: usize,
+    height: usize,
+    /// Bytes per sample, 1 or 2.
+    sample_len: usize,
+    samples_per_pixel: usize,
+    /// Samples of a pixel that carry payload bits, alpha is left alone.
+    color_samples: usize,
+}
+
+impl Layout {
+    fn from_png(png: &Png) -> Result<Layout, Error> {
+        let ihdr = png.ihdr()?;
+
+        let color_samples = match ihdr.color_type {
+            ColorType::Grayscale => 1,
+            ColorType::Rgb | ColorType::Rgba => 3,
+            _ => {
+                return Err(Error::Unsupported(format!(
+                    "LSB mode supports grayscale, RGB and RGBA, got {}",
+                    ihdr.color_type
+                )))
+            }
+        };
+
+        if ihdr.bit_depth != 8 && ihdr.bit_depth != 16 {
+            return Err(Error::Unsupported(format!(
+                "LSB mode supports 8 and 16 bit depth, got {}",
+                ihdr.bit_depth
+            )));
+        }
+
+        if ihdr.is_interlaced() {
+            return Err(Error::Unsupported(
+                "LSB mode does not support interlaced images".into(),
+            ));
+        }
+
+        Ok(Layout {
+            width: ihdr.width as usize,
+            height: ihdr.height as usize,
+            sample_len: ihdr.bit_depth as usize / 8,
+            samples_per_pixel: ihdr.color_type.channels(),
+            color_samples,
+        })
+    }
+
+    fn bpp(&self) -> usize {
+        self.sample_len * self.samples_per_pixel
+    }
+
+    fn stride(&self) -> usize {
+        self.width * self.bpp()
+    }
+
+    /// Byte offsets in the raw rows whose lowest bit carries the payload, in order.
+    fn positions(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.width * self.height).flat_map(move |pixel| {
+            (0..self.color_samples)
+                .map(move |sample| pixel * self.bpp() + (sample + 1) * self.sample_len - 1)
+        })
+    }
+
+    /// Payload bytes that fit, not counting the header.
+    fn capacity(&self) -> usize {
+        (self.width * self.height * self.color_samples / 8).saturating_sub(HEADER_LEN)
+    }
+}
+
+fn read_raster(png: &Png, layout: &Layout) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let mut inflated = Vec::new();
+
+    // One byte past the expected rows is enough to tell the data is too long.
+    ZlibDecoder::new(png.image_data().as_slice())
+        .take(png.ihdr()?.image_data_len() + 1)
+        .read_to_end(&mut inflated)?;
+
+    let (raw, filter_types) = scanline::unfilter(&inflated, layout.stride(), layout.bpp())?;
+
+    if raw.len() != layout.stride() * layout.height {
+        return Err(Error::InvalidData(format!(
+            "Expect {} rows of image data, got {}",
+            layout.height,
+            filter_types.len()
+        )));
+    }
+
+    Ok((raw, filter_types))
+}
+
+/// Payload bytes that can be hidden in the pixels of `png`.
+pub fn capacity(png: &Png) -> Result<usize, Error> {
+    Layout::from_png(png).map(|layout| layout.capacity())
+}
+
+/// Hide `payload` in the lowest bit of every color sample of `png`.
+pub fn embed(png: &mut Png, payload: &[u8]) -> Result<(), Error> {
+    let layout = Layout::from_png(png)?;
+
+    if payload.len() > layout.capacity() {
+        return Err(Error::InvalidInput(format!(
+            "Payload of {} byte(s) exceeds LSB capacity of {} byte(s)",
+            payload.len(),
+            layout.capacity()
+        )));
+    }
+
+    let (mut raw, filter_types) = read_raster(png, &layout)?;
+    let framed = [
+        MAGIC.as_slice(),
+        &(payload.len() as u32).to_be_bytes(),
+        payload,
+    ]
+    .concat();
+    let bits = framed
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |shift| (byte >> shift) & 1));
+
+    for (pos, bit) in layout.positions().zip(bits) {
+        raw[pos] = (raw[pos] & !1) | bit;
+    }
+
+    let filtered = scanline::filter(&raw, layout.stride(), layout.bpp(), &filter_types);
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+
+    encoder.write_all(&filtered)?;
+    png.set_image_data(&encoder.finish()?, IDAT_LEN)
+}
+
+/// Read back a payload hidden by [`embed`].
+pub fn extract(png: &Png) -> Result<Vec<u8>, Error> {
+    let layout = Layout::from_png(png)?;
+    let (raw, _) = read_raster(png, &layout)?;
+    let mut bytes = layout
+        .positions()
+        .map(|pos| raw[pos] & 1)
+        .collect::<Vec<u8>>()
+        .chunks_exact(8)
+        .map(|bits| bits.iter().fold(0u8, |byte, bit| (byte << 1) | bit))
+        .collect::<Vec<u8>>();
+
+    if bytes.len() < HEADER_LEN || bytes[..4] != MAGIC {
+        return Err(Error::NotFound("No LSB payload found".into()));
+    }
+
+    let len = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+
+    if len > layout.capacity() {
+        return Err(Error::InvalidData(format!(
+            "LSB payload claims {} byte(s), capacity is {} byte(s)",
+            len,
+            layout.capacity()
+        )));
+    }
+
+    bytes.truncate(HEADER_LEN + len);
+
+    Ok(bytes.split_off(HEADER_LEN))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk::Chunk, chunk_type::ChunkType};
+    use std::str::FromStr;
+
+    fn testing_png(bit_depth: u8, color_type: u8, samples: usize) -> Png {
+        let (width, height) = (16u32, 8u32);
+        let stride = width as usize * samples * bit_depth as usize / 8;
+        let raw: Vec<u8> = (0..stride * height as usize)
+            .map(|i| (i * 7 % 256) as u8)
+            .collect();
+        let filtered = scanline::filter(
+            &raw,
+            stride,
+            samples * bit_depth as usize / 8,
+            &[scanline::FILTER_PAETH; 8],
+        );
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&filtered).unwrap();
+
+        let ihdr = [
+            width.to_be_bytes().as_slice(),
+            &height.to_be_bytes(),
+            &[bit_depth, color_type, 0, 0, 0],
+        ]
+        .concat();
+
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr),
+            Chunk::new(
+                ChunkType::from_str("IDAT").unwrap(),
+                encoder.finish().unwrap(),
+            ),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        for (bit_depth, color_type, samples) in [
+            (8, 0, 1),
+            (8, 2, 3),
+            (8, 6, 4),
+            (16, 0, 1),
+            (16, 2, 3),
+            (16, 6, 4),
+        ] {
+            let mut png = testing_png(bit_depth, color_type, samples);
+            let before = read_raster(&png, &Layout::from_png(&png).unwrap())
+                .unwrap()
+                .0;
+
+            embed(&mut png, b"secret").unwrap();
+            assert_eq!(extract(&png).unwrap(), b"secret");
+
+            // Only the lowest bits changed.
+            let after = read_raster(&png, &Layout::from_png(&png).unwrap())
+                .unwrap()
+                .0;
+            assert!(before.iter().zip(&after).all(|(a, b)| a & !1 == b & !1));
+        }
+    }
+
+    #[test]
+    fn test_alpha_is_untouched() {
+        let mut png = testing_png(8, 6, 4);
+        let before = read_raster(&png, &Layout::from_png(&png).unwrap())
+            .unwrap()
+            .0;
+
+        embed(&mut png, &[0xff; 40]).unwrap();
+
+        let after = read_raster(&png, &Layout::from_png(&png).unwrap())
+            .unwrap()
+            .0;
+        assert!(before
+            .iter()
+            .zip(&after)
+            .skip(3)
+            .step_by(4)
+            .all(|(a, b)| a == b));
+    }
+
+    #[test]
+    fn test_capacity() {
+        // 16 * 8 pixels * 3 samples / 8 bits - header
+        assert_eq!(capacity(&testing_png(8, 2, 3)).unwrap(), 48 - HEADER_LEN);
+        assert_eq!(capacity(&testing_png(16, 0, 1)).unwrap(), 16 - HEADER_LEN);
+
+        let mut png = testing_png(16, 0, 1);
+        assert!(embed(&mut png, &[0; 9]).is_err());
+    }
+
+    #[test]
+    fn test_extract_without_payload() {
+        assert!(extract(&testing_png(8, 2, 3)).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_color_type() {
+        let mut png = testing_png(8, 3, 1);
+        assert!(embed(&mut png, b"secret").is_err());
+    }
+}
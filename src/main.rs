@@ -1,22 +1,44 @@
 mod args;
-mod chunk;
-mod chunk_type;
+mod batch;
 mod commands;
-mod png;
 
 use clap::Parser;
 use commands::{Cmd, Commands};
-use std::io::Error;
+use cphoto::Error;
+use std::process::ExitCode;
 
-fn main() -> Result<(), Error> {
-    let cmds = Commands::parse();
-
-    match cmds.cmd {
+fn run(cmd: Cmd) -> Result<(), Error> {
+    match cmd {
+        Cmd::Encode(args) if batch::is_batch(&args.file_path, &args.batch) => batch::encode(&args)?,
         Cmd::Encode(args) => Commands::encode(&args)?,
-        Cmd::Decode(args) => println!("Decode message:\n{}", Commands::decode(&args)?),
+        Cmd::Decode(args) if batch::is_batch(&args.file_path, &args.batch) => batch::decode(&args)?,
+        Cmd::Decode(args) => Commands::decode(&args)?,
         Cmd::Remove(args) => Commands::remove(&args)?,
         Cmd::Print(args) => println!("{}", Commands::print(&args)?),
+        Cmd::Validate(args) => Commands::validate(&args)?,
+        Cmd::Text(args) => Commands::text(&args)?,
+        Cmd::Recover(args) => Commands::recover(&args)?,
+        Cmd::Repair(args) => Commands::repair(&args)?,
+        Cmd::Keygen(args) => Commands::keygen(&args)?,
+        Cmd::Sign(args) => Commands::sign(&args)?,
+        Cmd::Verify(args) => Commands::verify(&args)?,
+        Cmd::Diff(args) => Commands::diff(&args)?,
+        Cmd::Patch(args) => Commands::patch(&args)?,
+        Cmd::Strip(args) => Commands::strip(&args)?,
+        Cmd::Optimize(args) => Commands::optimize(&args)?,
     };
 
     Ok(())
 }
+
+fn main() -> ExitCode {
+    let cmds = Commands::parse();
+
+    match run(cmds.cmd) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::Error,
+    ihdr::{ColorType, Ihdr},
+    png::Png,
+    scanline,
+    sign::{Signature, SIGNATURE_CHUNK_TYPE},
+};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+
+/// How the filter type of every row is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStrategy {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+    /// Per row, the type with the smallest sum of absolute values.
+    MinSum,
+    /// Per row, the type whose output has the lowest entropy.
+    Adaptive,
+}
+
+impl FilterStrategy {
+    pub const ALL: [FilterStrategy; 7] = [
+        Self::None,
+        Self::Sub,
+        Self::Up,
+        Self::Average,
+        Self::Paeth,
+        Self::MinSum,
+        Self::Adaptive,
+    ];
+
+    /// What the PNG spec recommends: no filter for palette images and samples below 8 bits,
+    /// filters picked per row otherwise.
+    pub fn recommended(ihdr: &Ihdr) -> FilterStrategy {
+        match ihdr.color_type == ColorType::Indexed || ihdr.bit_depth < 8 {
+            true => Self::None,
+            false => Self::MinSum,
+        }
+    }
+
+    /// Filter raw rows of `stride` bytes.
+    pub fn filter(&self, raw: &[u8], stride: usize, bpp: usize) -> Vec<u8> {
+        let fixed = |filter_type: u8| {
+            scanline::filter(raw, stride, bpp, &vec![filter_type; raw.len() / stride])
+        };
+
+        match self {
+            Self::None => fixed(scanline::FILTER_NONE),
+            Self::Sub => fixed(scanline::FILTER_SUB),
+            Self::Up => fixed(scanline::FILTER_UP),
+            Self::Average => fixed(scanline::FILTER_AVERAGE),
+            Self::Paeth => fixed(scanline::FILTER_PAETH),
+            Self::MinSum => scanline::filter_min_sum(raw, stride, bpp),
+            Self::Adaptive => scanline::filter_cheapest(raw, stride, bpp, scanline::entropy),
+        }
+    }
+}
+
+impl FromStr for FilterStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "sub" => Ok(Self::Sub),
+            "up" => Ok(Self::Up),
+            "average" | "avg" => Ok(Self::Average),
+            "paeth" => Ok(Self::Paeth),
+            "min-sum" => Ok(Self::MinSum),
+            "adaptive" => Ok(Self::Adaptive),
+            _ => Err(Error::InvalidInput(format!(
+                "Unknown filter: {}, expect none, sub, up, average, paeth, min-sum or adaptive",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for FilterStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Sub => write!(f, "sub"),
+            Self::Up => write!(f, "up"),
+            Self::Average => write!(f, "average"),
+            Self::Paeth => write!(f, "paeth"),
+            Self::MinSum => write!(f, "min-sum"),
+            Self::Adaptive => write!(f, "adaptive"),
+        }
+    }
+}
+
+/// The pixels of a PNG, as the unfiltered rows of every pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raster {
+    bpp: usize,
+    /// Stride and raw rows of every pass.
+    passes: Vec<(usize, Vec<u8>)>,
+}
+
+impl Raster {
+    /// Inflate and unfilter the image data of `png`, bytes after the last pass are dropped.
+    pub fn from_png(png: &Png) -> Result<Raster, Error> {
+        let ihdr = png.ihdr()?;
+        let bpp = ihdr.filter_bpp();
+        let mut inflated = Vec::new();
+
+        // Nothing past the last pass is used, so inflating stops there whatever the data holds.
+        ZlibDecoder::new(png.image_data().as_slice())
+            .take(ihdr.image_data_len())
+            .read_to_end(&mut inflated)?;
+
+        let mut passes = Vec::new();
+        let mut rest = inflated.as_slice();
+
+        for (stride, rows) in ihdr.passes() {
+            let len = (stride + 1) * rows;
+
+            if rest.len() < len {
+                return Err(Error::InvalidData(format!(
+                    "Image data of {} byte(s) is too short for a {} image",
+                    inflated.len(),
+                    ihdr
+                )));
+            }
+
+            let (pass, tail) = rest.split_at(len);
+
+            passes.push((stride, scanline::unfilter(pass, stride, bpp)?.0));
+            rest = tail;
+        }
+
+        Ok(Raster { bpp, passes })
+    }
+
+    /// Filter every pass with `strategy`, ready to be deflated.
+    pub fn filter(&self, strategy: FilterStrategy) -> Vec<u8> {
+        self.passes
+            .iter()
+            .flat_map(|(stride, raw)| strategy.filter(raw, *stride, self.bpp))
+            .collect()
+    }
+}
+
+pub fn deflate(data: &[u8], level: u32) -> Result<Vec<u8>, Error> {
+    if level > 9 {
+        return Err(Error::InvalidInput(format!(
+            "Deflate level {} is out of range, expect 0 to 9",
+            level
+        )));
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+
+    encoder.write_all(data)?;
+
+    Ok(encoder.finish()?)
+}
+
+/// Chunk length that spreads `len` bytes over as few chunks of at most `max_len` bytes as
+/// possible, all of them but the last one the same size.
+pub(crate) fn even_chunk_len(len: usize, max_len: usize) -> usize {
+    len.div_ceil(len.div_ceil(max_len).max(1))
+}
+
+/// What [`optimize`] tries.
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub strategies: Vec<FilterStrategy>,
+    pub levels: Vec<u32>,
+    /// Ancillary chunks to keep, every chunk is kept when `None`. A `siGN` chunk covering
+    /// `IDAT` is dropped either way once the image data is rewritten.
+    pub keep: Option<Vec<ChunkType>>,
+    /// Largest `IDAT` chunk to write, when the image data is rewritten.
+    pub idat_len: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            strategies: FilterStrategy::ALL.to_vec(),
+            levels: vec![6, 9],
+            keep: None,
+            idat_len: crate::strip::IDAT_LEN,
+        }
+    }
+}
+
+pub struct Optimized {
+    pub png: Png,
+    /// The winning filter strategy and deflate level, `None` when the original image data
+    /// was already the smallest.
+    pub best: Option<(FilterStrategy, u32)>,
+    /// Size of the image data before and after.
+    pub image_data_len: (usize, usize),
+    /// The dropped ancillary chunks, in file order.
+    pub removed: Vec<Chunk>,
+}
+
+/// Deflate the image data with every filter strategy and level of `options` and keep the
+/// smallest result, the pixels are left unchanged.
+///
+/// The original `IDAT` chunks are kept as they are when no trial beats them, along with any
+/// signature over them.
+pub fn optimize(png: &Png, options: &Options) -> Result<Optimized, Error> {
+    if options.strategies.is_empty() || options.levels.is_empty() {
+        return Err(Error::InvalidInput(
+            "Optimizing needs at least one filter and one deflate level".into(),
+        ));
+    }
+
+    if options.idat_len == 0 {
+        return Err(Error::InvalidInput("IDAT size must not be 0".into()));
+    }
+
+    let raster = Raster::from_png(png)?;
+    let original = png.image_data();
+    let mut best: Option<(FilterStrategy, u32, Vec<u8>)> = None;
+
+    for &strategy in &options.strategies {
+        let filtered = raster.filter(strategy);
+
+        for &level in &options.levels {
+            let data = deflate(&filtered, level)?;
+
+            if best
+                .as_ref()
+                .is_none_or(|(_, _, best)| data.len() < best.len())
+            {
+                best = Some((strategy, level, data));
+            }
+        }
+    }
+
+    let best = best.filter(|(_, _, data)| data.len() < original.len());
+    // New image data breaks a signature over IDAT, so it goes even when every chunk is kept.
+    let signed_idat = |c: &Chunk| {
+        c.chunk_type().to_string() == SIGNATURE_CHUNK_TYPE
+            && Signature::try_from(c.data()).map_or(true, |s| {
+                s.coverage.covers(&ChunkType::from_str("IDAT").unwrap())
+            })
+    };
+    let (kept, removed): (Vec<Chunk>, Vec<Chunk>) = png.chunks().iter().cloned().partition(|c| {
+        let kept = match &options.keep {
+            Some(keep) => c.chunk_type().is_critical() || keep.contains(c.chunk_type()),
+            None => true,
+        };
+
+        kept && !(best.is_some() && signed_idat(c))
+    });
+    let mut optimized = Png::from_chunks(kept);
+    let data = match &best {
+        Some((_, _, data)) => {
+            optimized.set_image_data(data, even_chunk_len(data.len(), options.idat_len))?;
+            data
+        }
+        None => &original,
+    };
+
+    Ok(Optimized {
+        png: optimized,
+        image_data_len: (original.len(), data.len()),
+        best: best.map(|(strategy, level, _)| (strategy, level)),
+        removed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sign::{self, Coverage};
+
+    fn testing_png(interlace: u8) -> Png {
+        let ihdr = Ihdr {
+            width: 40,
+            height: 24,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            compression: 0,
+            filter: 0,
+            interlace,
+        };
+        let filtered: Vec<u8> = ihdr
+            .passes()
+            .into_iter()
+            .flat_map(|(stride, rows)| {
+                let raw: Vec<u8> = (0..stride * rows)
+                    .map(|i| ((i % stride) / 4 * 3 + i / stride * 5) as u8)
+                    .collect();
+
+                scanline::filter(&raw, stride, 4, &vec![scanline::FILTER_NONE; rows])
+            })
+            .collect();
+        let chunk = |chunk_type: &str, data: &[u8]| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+        };
+
+        Png::builder()
+            .ihdr(&ihdr)
+            .chunk(chunk("tEXt", b"Comment\0hello"))
+            .chunks(
+                deflate(&filtered, 0)
+                    .unwrap()
+                    .chunks(1000)
+                    .map(|part| chunk("IDAT", part)),
+            )
+            .chunk(chunk("ruSt", b"payload"))
+            .build()
+    }
+
+    #[test]
+    fn test_optimize_keeps_pixels() {
+        for interlace in [0, 1] {
+            let png = testing_png(interlace);
+            let optimized = optimize(&png, &Options::default()).unwrap();
+            let (before, after) = optimized.image_data_len;
+
+            assert!(optimized.best.is_some());
+            assert!(after < before / 4);
+            assert_eq!(after, optimized.png.image_data().len());
+            assert_eq!(
+                Raster::from_png(&optimized.png).unwrap(),
+                Raster::from_png(&png).unwrap()
+            );
+            assert_eq!(optimized.png.chunks().len(), 5);
+            assert!(optimized.removed.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_optimize_keep() {
+        let options = Options {
+            strategies: vec![FilterStrategy::Paeth],
+            levels: vec![9],
+            keep: Some(vec![ChunkType::from_str("ruSt").unwrap()]),
+            ..Options::default()
+        };
+        let optimized = optimize(&testing_png(0), &options).unwrap();
+
+        assert_eq!(optimized.best, Some((FilterStrategy::Paeth, 9)));
+        assert_eq!(optimized.removed.len(), 1);
+        assert_eq!(optimized.removed[0].chunk_type().to_string(), "tEXt");
+        assert!(optimized.png.chunk_by_type("ruSt").is_some());
+    }
+
+    #[test]
+    fn test_optimize_drops_signature() {
+        let key = sign::generate_key();
+        let mut png = testing_png(0);
+
+        sign::sign(&mut png, &key, Coverage::Critical).unwrap();
+
+        let optimized = optimize(&png, &Options::default()).unwrap();
+
+        assert!(optimized.best.is_some());
+        assert_eq!(optimized.removed.len(), 1);
+        assert_eq!(optimized.removed[0].chunk_type().to_string(), "siGN");
+        assert!(optimized.png.chunk_by_type("siGN").is_none());
+
+        // A signature that leaves IDAT out stays valid.
+        let mut png = testing_png(0);
+        let coverage = Coverage::Types(vec![ChunkType::from_str("ruSt").unwrap()]);
+
+        sign::sign(&mut png, &key, coverage).unwrap();
+
+        let optimized = optimize(&png, &Options::default()).unwrap();
+
+        assert!(optimized.removed.is_empty());
+        assert!(sign::verify(&optimized.png, &sign::public_key(&key)).is_ok());
+    }
+
+    #[test]
+    fn test_optimize_keeps_signed_chunks() {
+        let key = sign::generate_key();
+        let mut png = testing_png(0);
+
+        sign::sign(&mut png, &key, Coverage::Critical).unwrap();
+
+        // Nothing beats the original, split over more IDAT chunks than idat_len would give.
+        let options = Options {
+            strategies: vec![FilterStrategy::None],
+            levels: vec![0],
+            ..Options::default()
+        };
+        let optimized = optimize(&png, &options).unwrap();
+
+        assert_eq!(optimized.best, None);
+        assert!(optimized.removed.is_empty());
+        assert!(png.chunks_by_type("IDAT").len() > 1);
+        assert_eq!(optimized.png.as_bytes(), png.as_bytes());
+        assert!(sign::verify(&optimized.png, &sign::public_key(&key)).is_ok());
+    }
+
+    #[test]
+    fn test_optimize_never_grows() {
+        let png = optimize(&testing_png(0), &Options::default()).unwrap().png;
+        let options = Options {
+            strategies: vec![FilterStrategy::None],
+            levels: vec![0],
+            ..Options::default()
+        };
+        let optimized = optimize(&png, &options).unwrap();
+
+        assert_eq!(optimized.best, None);
+        assert_eq!(optimized.png.as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_optimize_invalid_options() {
+        let png = testing_png(0);
+
+        for options in [
+            Options {
+                levels: vec![10],
+                ..Options::default()
+            },
+            Options {
+                strategies: vec![],
+                ..Options::default()
+            },
+            Options {
+                idat_len: 0,
+                ..Options::default()
+            },
+        ] {
+            assert!(matches!(
+                optimize(&png, &options),
+                Err(Error::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_filter_strategy_names() {
+        for strategy in FilterStrategy::ALL {
+            assert_eq!(
+                FilterStrategy::from_str(&strategy.to_string()).unwrap(),
+                strategy
+            );
+        }
+
+        assert_eq!(
+            FilterStrategy::from_str("avg").unwrap(),
+            FilterStrategy::Average
+        );
+        assert!(FilterStrategy::from_str("best").is_err());
+    }
+}
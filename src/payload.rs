@@ -0,0 +1,234 @@
+use crate::{
+    compress::Algorithm,
+    error::Error,
+    sequence::{self, Piece, Reassembled},
+};
+
+const VERSION: u8 = 1;
+/// version + flags + compression
+pub(crate) const HEADER_LEN: usize = 1 + 1 + 1;
+/// What a piece of a split payload adds in front of its data.
+pub(crate) const PIECE_OVERHEAD: usize = HEADER_LEN + sequence::HEADER_LEN;
+const FLAG_ENVELOPED: u8 = 1;
+const FLAG_SPLIT: u8 = 2;
+const FLAG_ENCRYPTED: u8 = 4;
+const KNOWN_FLAGS: u8 = FLAG_ENVELOPED | FLAG_SPLIT | FLAG_ENCRYPTED;
+
+/// How encode packed a payload, written in front of it so decode never guesses from the data.
+///
+/// Laid out as `version | flags | compression`, the `enveloped` flag says the data is an
+/// [`Envelope`](crate::envelope::Envelope) rather than the raw message, the `encrypted` flag
+/// that it was sealed with a passphrase, and `compression` is the id of the [`Algorithm`] the
+/// data was compressed with, or 0. A split payload repeats the header in front of every
+/// [`Piece`](crate::sequence::Piece), with the `split` flag set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Header {
+    pub enveloped: bool,
+    pub split: bool,
+    pub encrypted: bool,
+    pub compression: Option<Algorithm>,
+}
+
+impl Header {
+    fn flags(&self) -> u8 {
+        let mut flags = 0;
+
+        if self.enveloped {
+            flags |= FLAG_ENVELOPED;
+        }
+
+        if self.split {
+            flags |= FLAG_SPLIT;
+        }
+
+        if self.encrypted {
+            flags |= FLAG_ENCRYPTED;
+        }
+
+        flags
+    }
+
+    /// `data` with this header in front of it.
+    pub fn frame(&self, data: &[u8]) -> Vec<u8> {
+        let compression = self.compression.map_or(0, |algorithm| algorithm.id());
+
+        [[VERSION, self.flags(), compression].as_slice(), data].concat()
+    }
+
+    /// Split the header off the front of `bytes`, returning it with the data behind it.
+    pub fn parse(bytes: &[u8]) -> Result<(Header, &[u8]), Error> {
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::InvalidData("Payload header is truncated".into()));
+        }
+
+        if bytes[0] != VERSION {
+            return Err(Error::UnsupportedVersion {
+                what: "payload",
+                version: bytes[0],
+            });
+        }
+
+        let flags = bytes[1];
+
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(Error::InvalidData(format!(
+                "Unknown payload flags: {:#04x}",
+                flags & !KNOWN_FLAGS
+            )));
+        }
+
+        let header = Header {
+            enveloped: flags & FLAG_ENVELOPED != 0,
+            split: flags & FLAG_SPLIT != 0,
+            encrypted: flags & FLAG_ENCRYPTED != 0,
+            compression: match bytes[2] {
+                0 => None,
+                id => Some(Algorithm::try_from(id)?),
+            },
+        };
+
+        Ok((header, &bytes[HEADER_LEN..]))
+    }
+}
+
+/// The data of the chunks or segments carrying `data`, in pieces of at most `split_size` bytes
+/// when given. The `split` flag of `header` is set to match.
+pub fn pack(header: Header, data: &[u8], split_size: Option<usize>) -> Result<Vec<Vec<u8>>, Error> {
+    let Some(split_size) = split_size else {
+        return Ok(vec![Header {
+            split: false,
+            ..header
+        }
+        .frame(data)]);
+    };
+    let header = Header {
+        split: true,
+        ..header
+    };
+
+    Ok(Piece::split(data, split_size)?
+        .iter()
+        .map(|piece| header.frame(&piece.as_bytes()))
+        .collect())
+}
+
+/// Put a payload made by [`pack`] back together from the data of the chunks or segments that
+/// may carry it, in file order.
+///
+/// The first part decides the header, parts of other payloads are left alone.
+pub fn unpack(parts: &[&[u8]]) -> Result<(Header, Reassembled), Error> {
+    let first = parts
+        .first()
+        .ok_or_else(|| Error::NotFound("No payload found".into()))?;
+    let (header, data) = Header::parse(first)?;
+
+    if !header.split {
+        let reassembled = Reassembled {
+            data: data.to_vec(),
+            duplicated: Vec::new(),
+        };
+
+        return Ok((header, reassembled));
+    }
+
+    let pieces = parts
+        .iter()
+        .filter_map(|part| Header::parse(part).ok())
+        .filter(|(other, _)| *other == header)
+        .map(|(_, data)| Piece::try_from(data))
+        .collect::<Result<Vec<Piece>, Error>>()?;
+
+    Ok((header, Piece::reassemble(&pieces)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip() {
+        for (enveloped, split, encrypted, compression) in [
+            (false, false, false, None),
+            (true, false, true, Some(Algorithm::Zlib)),
+            (false, true, false, Some(Algorithm::Zstd)),
+            (true, true, true, None),
+        ] {
+            let header = Header {
+                enveloped,
+                split,
+                encrypted,
+                compression,
+            };
+            let framed = header.frame(b"data");
+            assert_eq!(
+                Header::parse(&framed).unwrap(),
+                (header, b"data".as_slice())
+            );
+        }
+    }
+
+    #[test]
+    fn test_raw_data_looking_like_a_known_format() {
+        // The header alone decides, whatever the data starts with.
+        for data in [
+            b"CPEV\x01hello".as_slice(),
+            b"CPSQ\x01hello",
+            b"CPCZ\x01\x01\0\0\0\0\0\0\0\x05hello",
+        ] {
+            let framed = Header::default().frame(data);
+            assert_eq!(Header::parse(&framed).unwrap(), (Header::default(), data));
+        }
+    }
+
+    #[test]
+    fn test_pack_and_unpack() {
+        let header = Header {
+            enveloped: true,
+            split: false,
+            encrypted: false,
+            compression: Some(Algorithm::Zlib),
+        };
+
+        let parts = pack(header, b"secret", None).unwrap();
+        let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+        let (actual, reassembled) = unpack(&parts).unwrap();
+        assert_eq!(actual, header);
+        assert_eq!(reassembled.data, b"secret");
+
+        let mut parts = pack(header, b"secret", Some(4)).unwrap();
+        parts.reverse();
+        // A part of an unrelated payload in between is left alone.
+        parts.insert(1, pack(header, b"other", None).unwrap().remove(0));
+        let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+        let (actual, reassembled) = unpack(&parts).unwrap();
+        assert!(actual.split);
+        assert_eq!(reassembled.data, b"secret");
+
+        let parts = pack(header, b"secret", Some(2)).unwrap();
+        assert!(matches!(
+            unpack(&[parts[0].as_slice(), &parts[2]]),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(unpack(&[]), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        assert!(matches!(
+            Header::parse(b"\x01\x00"),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(matches!(
+            Header::parse(b"\x09\x00\x00data"),
+            Err(Error::UnsupportedVersion { .. })
+        ));
+        assert!(matches!(
+            Header::parse(b"\x01\x80\x00data"),
+            Err(Error::InvalidData(_))
+        ));
+        assert!(matches!(
+            Header::parse(b"\x01\x00\x09data"),
+            Err(Error::Unsupported(_))
+        ));
+    }
+}
@@ -1,68 +1,100 @@
-use crate::chunk::Chunk;
-use std::io::{BufReader, Error, ErrorKind, Read};
+use crate::{
+    apng::{self, Animation, FrameTracker},
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::Error,
+    ihdr::Ihdr,
+    stream::{PngReader, PngWriter},
+    text::TextEntry,
+    validate::{self, Diagnostic},
+};
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
 
 pub struct Png {
-    pub header: [u8; 8],
-    pub chunks: Vec<Chunk>,
+    header: [u8; 8],
+    chunks: Vec<Chunk>,
 }
 
-fn chunk_reader(reader: &mut BufReader<&[u8]>) -> Result<Chunk, Error> {
-    let mut c_len = [0u8; 4];
-    let mut c_type = [0u8; 4];
-    let mut c_crc = [0u8; 4];
+/// Builds a [`Png`] chunk by chunk, `IEND` is appended unless it is already the last chunk.
+#[derive(Default)]
+pub struct PngBuilder {
+    chunks: Vec<Chunk>,
+}
+
+impl PngBuilder {
+    /// Add an `IHDR` chunk for `ihdr`.
+    pub fn ihdr(self, ihdr: &Ihdr) -> Self {
+        self.chunk(ihdr.to_chunk())
+    }
 
-    reader.read_exact(&mut c_len)?;
-    reader.read_exact(&mut c_type)?;
+    pub fn chunk(mut self, chunk: Chunk) -> Self {
+        self.chunks.push(chunk);
+        self
+    }
 
-    let mut c_data = vec![0u8; u32::from_be_bytes(c_len) as usize];
+    pub fn chunks(mut self, chunks: impl IntoIterator<Item = Chunk>) -> Self {
+        self.chunks.extend(chunks);
+        self
+    }
 
-    reader.read_exact(&mut c_data)?;
-    reader.read_exact(&mut c_crc)?;
+    pub fn build(mut self) -> Png {
+        if self.chunks.last().map(|c| c.chunk_type().bytes()) != Some(*b"IEND") {
+            self.chunks
+                .push(Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()));
+        }
 
-    Ok(Chunk::try_from(
-        [
-            c_len.as_slice(),
-            c_type.as_slice(),
-            &c_data,
-            c_crc.as_slice(),
-        ]
-        .concat()
-        .as_slice(),
-    )?)
+        Png::from_chunks(self.chunks)
+    }
 }
 
-impl TryFrom<&[u8]> for Png {
-    type Error = Error;
+/// The error for an index that would put a chunk before `IHDR` or after `IEND`.
+pub(crate) fn outside_image(index: usize) -> Error {
+    Error::InvalidInput(format!(
+        "Index {} would place the chunk before IHDR or after IEND",
+        index
+    ))
+}
 
-    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let mut reader = BufReader::new(value);
-        let mut header = [0u8; 8];
+/// Where a new chunk is placed inside a PNG.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkPosition {
+    /// Right before `IEND`, or at the end when there is no `IEND`.
+    BeforeIend,
+    /// Right after `IHDR`.
+    AfterIhdr,
+    /// Right before the first `IDAT`.
+    BeforeIdat,
+    /// At the given chunk index, which has to lie after `IHDR` and no later than `IEND`.
+    Index(usize),
+}
 
-        reader.read_exact(&mut header)?;
+impl FromStr for ChunkPosition {
+    type Err = Error;
 
-        if header != Self::STANDARD_HEADER {
-            return Err(Error::new(
-                ErrorKind::Unsupported,
-                format!("PNG file head: {:?} is invalid", header),
-            ));
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "before-iend" => Ok(Self::BeforeIend),
+            "after-ihdr" => Ok(Self::AfterIhdr),
+            "before-idat" => Ok(Self::BeforeIdat),
+            _ => match s.parse::<usize>() {
+                Ok(idx) => Ok(Self::Index(idx)),
+                Err(_) => Err(Error::InvalidInput(format!(
+                    "Unsupported position: {}, expect before-iend, after-ihdr, before-idat or an index",
+                    s
+                ))),
+            },
         }
+    }
+}
 
-        let head_chunk = chunk_reader(&mut reader)?;
-        let mut chunks = vec![head_chunk];
-
-        loop {
-            match chunk_reader(&mut reader) {
-                Ok(data_chunk) => chunks.push(data_chunk),
-                Err(err) => match err.kind() {
-                    ErrorKind::UnexpectedEof => break,
-                    _ => {
-                        return Err(Error::new(ErrorKind::Unsupported, "Invalid data."));
-                    }
-                },
-            }
-        }
+impl TryFrom<&[u8]> for Png {
+    type Error = Error;
 
-        Ok(Png { header, chunks })
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_reader(value)
     }
 }
 
@@ -73,7 +105,53 @@ impl std::fmt::Display for Png {
 }
 
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+    /// Read a whole PNG from `reader`, partial trailing bytes are ignored.
+    ///
+    /// See [`crate::recover::recover`] to salvage what parses out of a damaged file.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Png, Error> {
+        let mut reader = PngReader::new(reader)?;
+        let mut chunks = Vec::new();
+
+        loop {
+            let next = reader.next_header().and_then(|header| match header {
+                Some(_) => reader.read_chunk().map(Some),
+                None => Ok(None),
+            });
+
+            match next {
+                Ok(Some(chunk)) => chunks.push(chunk),
+                Ok(None) => break,
+                Err(err) if chunks.is_empty() => return Err(err),
+                Err(err) if err.is_truncated() => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        if chunks.is_empty() {
+            return Err(Error::NotFound("No chunk found".into()));
+        }
+
+        Ok(Png {
+            header: Self::STANDARD_HEADER,
+            chunks,
+        })
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<W, Error> {
+        let mut writer = PngWriter::new(writer)?;
+
+        for chunk in &self.chunks {
+            writer.write_chunk(chunk)?;
+        }
+
+        writer.finish()
+    }
+
+    pub fn builder() -> PngBuilder {
+        PngBuilder::default()
+    }
 
     pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
         Self {
@@ -86,45 +164,304 @@ impl Png {
         self.chunks.push(chunk);
     }
 
-    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, Error> {
-        match self
-            .chunks
+    /// Insert `chunk` at `position` and return its index.
+    ///
+    /// In an APNG the chunk never splits an `fcTL` from its frame data: before `IDAT` means
+    /// before the `fcTL` of the default image, and an index inside a frame moves past the
+    /// frame's data.
+    pub fn insert_chunk(&mut self, chunk: Chunk, position: ChunkPosition) -> Result<usize, Error> {
+        let idx = match position {
+            ChunkPosition::BeforeIend => self.position_of("IEND").unwrap_or(self.chunks.len()),
+            ChunkPosition::AfterIhdr => self.required_position_of("IHDR")? + 1,
+            ChunkPosition::BeforeIdat => {
+                let idx = self.required_position_of("IDAT")?;
+
+                match idx
+                    .checked_sub(1)
+                    .map(|prev| self.chunks[prev].chunk_type())
+                {
+                    Some(prev) if prev.to_string() == "fcTL" => idx - 1,
+                    _ => idx,
+                }
+            }
+            ChunkPosition::Index(mut idx) => {
+                if idx > self.chunks.len() {
+                    return Err(Error::IndexOutOfRange {
+                        index: idx,
+                        len: self.chunks.len(),
+                    });
+                }
+
+                let first = self.position_of("IHDR").map_or(0, |at| at + 1);
+                let last = self.position_of("IEND").unwrap_or(self.chunks.len());
+
+                if !(first..=last).contains(&idx) {
+                    return Err(outside_image(idx));
+                }
+
+                let mut tracker = FrameTracker::default();
+
+                self.chunks[..idx]
+                    .iter()
+                    .for_each(|c| tracker.push(&c.chunk_type().to_string()));
+
+                while idx < self.chunks.len()
+                    && tracker.splits(&self.chunks[idx].chunk_type().to_string())
+                {
+                    tracker.push(&self.chunks[idx].chunk_type().to_string());
+                    idx += 1;
+                }
+
+                idx
+            }
+        };
+
+        self.chunks.insert(idx, chunk);
+
+        Ok(idx)
+    }
+
+    /// Insert `chunks` next to each other, the first one at `position`.
+    pub fn insert_chunks(
+        &mut self,
+        chunks: Vec<Chunk>,
+        position: ChunkPosition,
+    ) -> Result<usize, Error> {
+        let mut chunks = chunks.into_iter();
+        let first = match chunks.next() {
+            Some(chunk) => self.insert_chunk(chunk, position)?,
+            None => return Ok(0),
+        };
+
+        for (offset, chunk) in chunks.enumerate() {
+            self.chunks.insert(first + offset + 1, chunk);
+        }
+
+        Ok(first)
+    }
+
+    fn position_of(&self, chunk_type: &str) -> Option<usize> {
+        self.chunks
             .iter()
-            .position(|c| c.c_type.to_string() == chunk_type)
-        {
-            Some(idx) => Ok(self.chunks.remove(idx)),
-            None => Err(Error::new(
-                ErrorKind::NotFound,
-                format!("Not any chunk's type is {}", chunk_type),
-            )),
+            .position(|c| c.chunk_type().to_string() == chunk_type)
+    }
+
+    fn required_position_of(&self, chunk_type: &str) -> Result<usize, Error> {
+        self.position_of(chunk_type)
+            .ok_or_else(|| Error::ChunkNotFound(chunk_type.into()))
+    }
+
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, Error> {
+        let idx = self.required_position_of(chunk_type)?;
+
+        Ok(self.chunks.remove(idx))
+    }
+
+    pub fn remove_chunks(&mut self, chunk_type: &str) -> Result<Vec<Chunk>, Error> {
+        self.required_position_of(chunk_type)?;
+
+        let (removed, kept) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|c| c.chunk_type().to_string() == chunk_type);
+
+        self.chunks = kept;
+
+        Ok(removed)
+    }
+
+    pub fn remove_chunks_at(&mut self, indices: &[usize]) -> Result<Vec<Chunk>, Error> {
+        let mut indices = indices.to_vec();
+
+        indices.sort_unstable();
+        indices.dedup();
+
+        if let Some(&idx) = indices.iter().find(|&&idx| idx >= self.chunks.len()) {
+            return Err(Error::IndexOutOfRange {
+                index: idx,
+                len: self.chunks.len(),
+            });
         }
+
+        let mut removed: Vec<Chunk> = indices
+            .iter()
+            .rev()
+            .map(|&idx| self.chunks.remove(idx))
+            .collect();
+
+        removed.reverse();
+
+        Ok(removed)
     }
 
     pub fn header(&self) -> &[u8; 8] {
         &self.header
     }
 
+    /// The parsed and checked image header.
+    pub fn ihdr(&self) -> Result<Ihdr, Error> {
+        let chunk = self
+            .chunk_by_type("IHDR")
+            .ok_or_else(|| Error::ChunkNotFound("IHDR".into()))?;
+
+        Ihdr::try_from(chunk)
+    }
+
+    /// Check chunk ordering against the PNG spec, an empty list means no problem was found.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        validate::validate(&self.chunks)
+    }
+
+    /// The animation of an APNG, `None` if there is no `acTL`.
+    pub fn animation(&self) -> Result<Option<Animation>, Error> {
+        apng::animation(&self.chunks)
+    }
+
     pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
 
+    /// Byte offset of every chunk from the start of the file.
+    pub fn chunk_offsets(&self) -> Vec<usize> {
+        self.chunks
+            .iter()
+            .scan(self.header.len(), |offset, c| {
+                let current = *offset;
+                *offset += 12 + c.length() as usize;
+                Some(current)
+            })
+            .collect()
+    }
+
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
         self.chunks
             .iter()
-            .find(|c| c.c_type.to_string() == chunk_type)
+            .find(|c| c.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Concatenated data of every `IDAT` chunk.
+    pub fn image_data(&self) -> Vec<u8> {
+        self.chunks_by_type("IDAT")
+            .into_iter()
+            .flat_map(|c| c.data().iter().copied())
+            .collect()
+    }
+
+    /// Replace every `IDAT` chunk with `data`, split into chunks of at most `chunk_len` bytes.
+    pub fn set_image_data(&mut self, data: &[u8], chunk_len: usize) -> Result<(), Error> {
+        let idx = self.required_position_of("IDAT")?;
+        let idat = ChunkType::from_str("IDAT")?;
+
+        self.chunks.retain(|c| *c.chunk_type() != idat);
+
+        let chunks = data
+            .chunks(chunk_len.max(1))
+            .map(|part| Chunk::new(idat.clone(), part.to_vec()))
+            .collect();
+
+        self.insert_chunks(chunks, ChunkPosition::Index(idx))
+            .map(|_| ())
+    }
+
+    pub fn chunks_by_type(&self, chunk_type: &str) -> Vec<&Chunk> {
+        self.chunks
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == chunk_type)
+            .collect()
+    }
+
+    pub fn chunk_mut(&mut self, index: usize) -> Option<&mut Chunk> {
+        self.chunks.get_mut(index)
+    }
+
+    /// The `n`th chunk of `chunk_type`, counting from 0.
+    pub fn nth_chunk_by_type_mut(
+        &mut self,
+        chunk_type: &str,
+        n: usize,
+    ) -> Result<&mut Chunk, Error> {
+        let len = self.chunks_by_type(chunk_type).len();
+
+        if len == 0 {
+            return Err(Error::ChunkNotFound(chunk_type.into()));
+        }
+
+        self.chunks
+            .iter_mut()
+            .filter(|c| c.chunk_type().to_string() == chunk_type)
+            .nth(n)
+            .ok_or(Error::IndexOutOfRange { index: n, len })
+    }
+
+    /// Replace the data of the `n`th chunk of `chunk_type`, returning the old data.
+    pub fn set_chunk_data(
+        &mut self,
+        chunk_type: &str,
+        n: usize,
+        data: Vec<u8>,
+    ) -> Result<Vec<u8>, Error> {
+        self.nth_chunk_by_type_mut(chunk_type, n)
+            .map(|c| c.set_data(data))
+    }
+
+    /// Append to the data of the `n`th chunk of `chunk_type`.
+    pub fn append_chunk_data(
+        &mut self,
+        chunk_type: &str,
+        n: usize,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.nth_chunk_by_type_mut(chunk_type, n)
+            .map(|c| c.append_data(data))
+    }
+
+    /// Change the type of the chunk at `index`, returning the old type.
+    pub fn set_chunk_type(
+        &mut self,
+        index: usize,
+        chunk_type: ChunkType,
+    ) -> Result<ChunkType, Error> {
+        let len = self.chunks.len();
+
+        self.chunk_mut(index)
+            .map(|c| c.set_type(chunk_type))
+            .ok_or(Error::IndexOutOfRange { index, len })
+    }
+
+    /// Every `tEXt`, `zTXt` and `iTXt` chunk parsed as a text entry, with its chunk index.
+    pub fn text_entries(&self) -> Vec<(usize, Result<TextEntry, Error>)> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| TextEntry::is_text_chunk(c))
+            .map(|(idx, c)| (idx, TextEntry::try_from(c)))
+            .collect()
+    }
+
+    /// Remove every text chunk whose keyword is `keyword`.
+    pub fn remove_text_entries(&mut self, keyword: &str) -> Result<Vec<Chunk>, Error> {
+        let indices: Vec<usize> = self
+            .text_entries()
+            .into_iter()
+            .filter(|(_, entry)| matches!(entry, Ok(entry) if entry.keyword == keyword))
+            .map(|(idx, _)| idx)
+            .collect();
+
+        if indices.is_empty() {
+            return Err(Error::NotFound(format!(
+                "Not any text entry's keyword is {}",
+                keyword
+            )));
+        }
+
+        self.remove_chunks_at(&indices)
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
         self.header
             .into_iter()
-            .chain(
-                self.chunks
-                    .iter()
-                    .fold(Vec::new(), |a, b| {
-                        a.into_iter().chain(b.as_bytes().into_iter()).collect()
-                    })
-                    .into_iter(),
-            )
+            .chain(self.chunks.iter().fold(Vec::new(), |a, b| {
+                a.into_iter().chain(b.as_bytes()).collect()
+            }))
             .collect()
     }
 }
@@ -134,6 +471,7 @@ mod tests {
     use super::*;
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
+    use crate::text::TextKind;
     use std::convert::TryFrom;
     use std::str::FromStr;
 
@@ -256,6 +594,206 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_chunk_offsets() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let offsets = png.chunk_offsets();
+        assert_eq!(offsets.len(), png.chunks().len());
+        assert_eq!(offsets[0], 8);
+        assert_eq!(offsets[1], 8 + 12 + 13);
+        assert_eq!(*offsets.last().unwrap(), PNG_FILE.len() - 12);
+    }
+
+    #[test]
+    fn test_remove_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "Another").unwrap());
+        let removed = png.remove_chunks("TeSt").unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(&removed[1].data_as_string().unwrap(), "Another");
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.remove_chunks("TeSt").is_err());
+    }
+
+    #[test]
+    fn test_remove_chunks_at() {
+        let mut png = testing_png();
+        let removed = png.remove_chunks_at(&[2, 0, 2]).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(&removed[0].chunk_type().to_string(), "FrSt");
+        assert_eq!(&removed[1].chunk_type().to_string(), "LASt");
+        assert_eq!(png.chunks().len(), 1);
+        assert_eq!(&png.chunks()[0].chunk_type().to_string(), "miDl");
+    }
+
+    #[test]
+    fn test_remove_chunks_at_out_of_range() {
+        let mut png = testing_png();
+        assert!(png.remove_chunks_at(&[0, 3]).is_err());
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_insert_chunk_before_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let len = png.chunks().len();
+        let idx = png
+            .insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                ChunkPosition::BeforeIend,
+            )
+            .unwrap();
+        assert_eq!(idx, len - 1);
+        assert_eq!(&png.chunks()[len].chunk_type().to_string(), "IEND");
+    }
+
+    #[test]
+    fn test_insert_chunk_without_iend() {
+        let mut png = testing_png();
+        let idx = png
+            .insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                ChunkPosition::BeforeIend,
+            )
+            .unwrap();
+        assert_eq!(idx, 3);
+    }
+
+    #[test]
+    fn test_insert_chunk_after_ihdr() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let idx = png
+            .insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                ChunkPosition::AfterIhdr,
+            )
+            .unwrap();
+        assert_eq!(idx, 1);
+        assert_eq!(&png.chunks()[0].chunk_type().to_string(), "IHDR");
+    }
+
+    #[test]
+    fn test_insert_chunk_before_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let idx = png
+            .insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                ChunkPosition::BeforeIdat,
+            )
+            .unwrap();
+        assert_eq!(&png.chunks()[idx + 1].chunk_type().to_string(), "IDAT");
+    }
+
+    #[test]
+    fn test_insert_chunk_at_index() {
+        let mut png = testing_png();
+        png.insert_chunk(
+            chunk_from_strings("TeSt", "Message").unwrap(),
+            ChunkPosition::Index(1),
+        )
+        .unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+
+        let res = png.insert_chunk(
+            chunk_from_strings("TeSt", "Message").unwrap(),
+            ChunkPosition::Index(10),
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_insert_chunk_outside_image() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let iend = png.chunks().len() - 1;
+
+        for idx in [0, iend + 1] {
+            let res = png.insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                ChunkPosition::Index(idx),
+            );
+            assert!(matches!(res, Err(Error::InvalidInput(_))));
+        }
+
+        png.insert_chunk(
+            chunk_from_strings("TeSt", "Message").unwrap(),
+            ChunkPosition::Index(iend),
+        )
+        .unwrap();
+        assert_eq!(png.chunks()[iend + 1].chunk_type().to_string(), "IEND");
+    }
+
+    #[test]
+    fn test_insert_chunks() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let len = png.chunks().len();
+        let idx = png
+            .insert_chunks(
+                vec![
+                    chunk_from_strings("TeSt", "first").unwrap(),
+                    chunk_from_strings("TeSt", "second").unwrap(),
+                ],
+                ChunkPosition::BeforeIend,
+            )
+            .unwrap();
+        assert_eq!(idx, len - 1);
+        assert_eq!(&png.chunks()[idx + 1].data_as_string().unwrap(), "second");
+        assert_eq!(&png.chunks()[idx + 2].chunk_type().to_string(), "IEND");
+
+        let chunks = png.chunks_by_type("TeSt");
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[0].data_as_string().unwrap(), "first");
+    }
+
+    #[test]
+    fn test_set_image_data() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let data = png.image_data();
+        assert_eq!(data.len(), 4681);
+
+        png.set_image_data(&data, 1000).unwrap();
+        let chunks = png.chunks_by_type("IDAT");
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks[4].length(), 681);
+        assert_eq!(&png.chunks()[4].chunk_type().to_string(), "IDAT");
+        assert_eq!(png.image_data(), data);
+
+        png.set_image_data(&data, 8192).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+        assert!(testing_png().set_image_data(&data, 8192).is_err());
+    }
+
+    #[test]
+    fn test_insert_chunk_missing_anchor() {
+        let mut png = testing_png();
+        let res = png.insert_chunk(
+            chunk_from_strings("TeSt", "Message").unwrap(),
+            ChunkPosition::BeforeIdat,
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_chunk_position_from_str() {
+        assert_eq!(
+            ChunkPosition::from_str("before-iend").unwrap(),
+            ChunkPosition::BeforeIend
+        );
+        assert_eq!(
+            ChunkPosition::from_str("after-ihdr").unwrap(),
+            ChunkPosition::AfterIhdr
+        );
+        assert_eq!(
+            ChunkPosition::from_str("before-idat").unwrap(),
+            ChunkPosition::BeforeIdat
+        );
+        assert_eq!(
+            ChunkPosition::from_str("3").unwrap(),
+            ChunkPosition::Index(3)
+        );
+        assert!(ChunkPosition::from_str("middle").is_err());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -270,6 +808,103 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_from_reader_and_write_to() {
+        let png = Png::from_reader(&PNG_FILE[..]).unwrap();
+        let bytes = png.write_to(Vec::new()).unwrap();
+        assert_eq!(bytes, PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_ihdr() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.ihdr().unwrap();
+        assert_eq!((ihdr.width, ihdr.height), (50, 50));
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, crate::ihdr::ColorType::Rgba);
+        assert!(!ihdr.is_interlaced());
+
+        assert!(testing_png().ihdr().is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        // The test file carries an unknown critical `RuSt` chunk, which is only a warning.
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png
+            .validate()
+            .iter()
+            .all(|d| d.severity == crate::validate::Severity::Warning));
+
+        assert!(!testing_png().validate().is_empty());
+    }
+
+    #[test]
+    fn test_edit_chunks() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("RuSt", "second").unwrap());
+
+        assert_eq!(
+            png.set_chunk_data("RuSt", 1, b"2nd".to_vec()).unwrap(),
+            b"second"
+        );
+        png.append_chunk_data("RuSt", 0, b" there").unwrap();
+        assert!(png.set_chunk_data("RuSt", 2, vec![]).is_err());
+        assert!(png.set_chunk_data("NoNe", 0, vec![]).is_err());
+
+        let old = png
+            .set_chunk_type(5, ChunkType::from_str("ruSt").unwrap())
+            .unwrap();
+        assert_eq!(old.to_string(), "RuSt");
+        assert!(png.set_chunk_type(99, old).is_err());
+
+        // Lengths and CRCs still hold after a round trip.
+        let png = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hey there");
+        assert_eq!(png.chunk_by_type("RuSt").unwrap().data(), b"2nd");
+    }
+
+    #[test]
+    fn test_png_builder() {
+        let ihdr = Png::try_from(&PNG_FILE[..]).unwrap().ihdr().unwrap();
+        let png = Png::builder().ihdr(&ihdr).chunks(testing_chunks()).build();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "FrSt", "miDl", "LASt", "IEND"]);
+
+        let png = Png::builder().chunk(png.chunks()[4].clone()).build();
+        assert_eq!(png.chunks().len(), 1);
+    }
+
+    #[test]
+    fn test_text_entries() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let entries = [
+            TextEntry::new(TextKind::Text, "Author".into(), "zrain".into()),
+            TextEntry::new(TextKind::Compressed, "Comment".into(), "hey".into()),
+            TextEntry::new(TextKind::Text, "Author".into(), "someone".into()),
+        ];
+
+        for entry in &entries {
+            png.insert_chunk(entry.to_chunk().unwrap(), ChunkPosition::BeforeIend)
+                .unwrap();
+        }
+
+        let parsed: Vec<TextEntry> = png
+            .text_entries()
+            .into_iter()
+            .map(|(_, entry)| entry.unwrap())
+            .collect();
+        assert_eq!(parsed, entries);
+
+        assert_eq!(png.remove_text_entries("Author").unwrap().len(), 2);
+        assert_eq!(png.text_entries().len(), 1);
+        assert!(png.remove_text_entries("Author").is_err());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -0,0 +1,362 @@
+use crate::{
+    chunk::{calc_crc, Chunk, MAX_LENGTH},
+    chunk_type::ChunkType,
+    png::Png,
+};
+/// length + type + CRC
+const FRAME_LEN: usize = 12;
+/// A resync gives up once the candidate chunks that failed their CRC check add up to this many
+/// bytes, so a large damaged region does not take quadratic time.
+pub(crate) const RESYNC_BUDGET: usize = 64 * 1024 * 1024;
+
+/// Why a chunk could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureReason {
+    /// The stored CRC does not match the type and data.
+    BadCrc { stored: u32, computed: u32 },
+    /// The type bytes are not a valid chunk type.
+    BadType([u8; 4]),
+    /// The length field exceeds 2^31 - 1.
+    BadLength(u32),
+    /// The data ends before the chunk does.
+    Truncated { needed: usize, available: usize },
+}
+
+impl std::fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadCrc { stored, computed } => write!(
+                f,
+                "CRC check failed, stored {:#010x}, computed {:#010x}",
+                stored, computed
+            ),
+            Self::BadType(bytes) => write!(f, "Invalid chunk type {:?}", bytes),
+            Self::BadLength(length) => write!(f, "Chunk length {} is too large", length),
+            Self::Truncated { needed, available } => write!(
+                f,
+                "Truncated, chunk needs {} byte(s), {} byte(s) left",
+                needed, available
+            ),
+        }
+    }
+}
+
+/// A place where parsing failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// Byte offset of the chunk that failed, from the start of the file.
+    pub offset: usize,
+    pub reason: FailureReason,
+    /// Bytes given up on, up to the next valid chunk or the end of the data.
+    pub skipped: usize,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "offset {}: {}, {} byte(s) skipped",
+            self.offset, self.reason, self.skipped
+        )
+    }
+}
+
+/// A chunk that parsed, with its byte offset from the start of the file.
+pub struct RecoveredChunk {
+    pub offset: usize,
+    pub chunk: Chunk,
+}
+
+/// Everything that could be salvaged from a damaged PNG.
+pub struct Recovery {
+    pub signature_valid: bool,
+    pub chunks: Vec<RecoveredChunk>,
+    pub failures: Vec<Failure>,
+    /// Bytes after `IEND`.
+    pub trailing: Vec<u8>,
+}
+
+impl Recovery {
+    /// Rebuild a PNG out of the recovered chunks.
+    pub fn to_png(&self) -> Png {
+        Png::from_chunks(self.chunks.iter().map(|c| c.chunk.clone()).collect())
+    }
+}
+
+/// Check the length and type of the chunk starting at `offset`, without looking at its data.
+fn header_at(bytes: &[u8], offset: usize) -> Result<(ChunkType, usize), FailureReason> {
+    let available = bytes.len() - offset;
+
+    if available < FRAME_LEN {
+        return Err(FailureReason::Truncated {
+            needed: FRAME_LEN,
+            available,
+        });
+    }
+
+    let length = u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let type_bytes: [u8; 4] = bytes[offset + 4..offset + 8].try_into().unwrap();
+    let chunk_type =
+        ChunkType::try_from(type_bytes).map_err(|_| FailureReason::BadType(type_bytes))?;
+
+    if length > MAX_LENGTH {
+        return Err(FailureReason::BadLength(length));
+    }
+
+    let needed = FRAME_LEN + length as usize;
+
+    if available < needed {
+        return Err(FailureReason::Truncated { needed, available });
+    }
+
+    Ok((chunk_type, length as usize))
+}
+
+/// Parse the chunk starting at `offset`.
+fn parse_at(bytes: &[u8], offset: usize) -> Result<Chunk, FailureReason> {
+    let (chunk_type, length) = header_at(bytes, offset)?;
+    let end = offset + FRAME_LEN + length;
+    let stored = u32::from_be_bytes(bytes[end - 4..end].try_into().unwrap());
+    // Type and data sit next to each other, so a bad chunk is checked without copying it.
+    let computed = calc_crc(&bytes[offset + 4..end - 4]);
+
+    if computed != stored {
+        return Err(FailureReason::BadCrc { stored, computed });
+    }
+
+    Ok(Chunk::new(chunk_type, bytes[offset + 8..end - 4].to_vec()))
+}
+
+/// Offset of the next valid chunk after `offset`, `None` if there is none or the search ran
+/// out of [`RESYNC_BUDGET`]. Only candidates that fail are charged, so a valid chunk of any
+/// size is still found.
+fn next_valid(bytes: &[u8], offset: usize) -> Option<usize> {
+    let mut budget = RESYNC_BUDGET;
+
+    for at in offset + 1..bytes.len() {
+        let Ok((_, length)) = header_at(bytes, at) else {
+            continue;
+        };
+
+        if parse_at(bytes, at).is_ok() {
+            return Some(at);
+        }
+
+        budget = budget.checked_sub(FRAME_LEN + length)?;
+    }
+
+    None
+}
+
+/// Salvage every chunk of `bytes` that parses.
+///
+/// Parsing stops at the first failure unless `resync` is set, then the bytes are scanned for
+/// the next valid chunk and parsing goes on from there. The scan gives up, skipping the rest,
+/// once the candidates that turned out bad add up to 64 MiB.
+pub fn recover(bytes: &[u8], resync: bool) -> Recovery {
+    let signature_valid = bytes.starts_with(&Png::STANDARD_HEADER);
+    let mut recovery = Recovery {
+        signature_valid,
+        chunks: Vec::new(),
+        failures: Vec::new(),
+        trailing: Vec::new(),
+    };
+    let mut offset = Png::STANDARD_HEADER.len().min(bytes.len());
+
+    while offset < bytes.len() {
+        match parse_at(bytes, offset) {
+            Ok(chunk) => {
+                let is_iend = chunk.chunk_type().bytes() == *b"IEND";
+                let len = FRAME_LEN + chunk.length() as usize;
+
+                recovery.chunks.push(RecoveredChunk { offset, chunk });
+                offset += len;
+
+                if is_iend {
+                    recovery.trailing = bytes[offset..].to_vec();
+                    break;
+                }
+            }
+            Err(reason) => {
+                let next = match resync {
+                    true => next_valid(bytes, offset),
+                    false => None,
+                };
+
+                recovery.failures.push(Failure {
+                    offset,
+                    reason,
+                    skipped: next.unwrap_or(bytes.len()) - offset,
+                });
+
+                match next {
+                    Some(next) => offset = next,
+                    None => break,
+                }
+            }
+        }
+    }
+
+    recovery
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::from_chunks(
+            [
+                ("IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+                ("tEXt", b"Comment\0hello".to_vec()),
+                ("IDAT", vec![1, 2, 3, 4]),
+                ("IEND", vec![]),
+            ]
+            .into_iter()
+            .map(|(t, data)| Chunk::new(ChunkType::from_str(t).unwrap(), data))
+            .collect(),
+        )
+        .as_bytes()
+    }
+
+    fn types(recovery: &Recovery) -> Vec<String> {
+        recovery
+            .chunks
+            .iter()
+            .map(|c| c.chunk.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_recover_intact() {
+        let recovery = recover(&testing_bytes(), false);
+        assert!(recovery.signature_valid);
+        assert_eq!(types(&recovery), ["IHDR", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(recovery.chunks[1].offset, 8 + 12 + 13);
+        assert!(recovery.failures.is_empty());
+        assert!(recovery.trailing.is_empty());
+        assert_eq!(recovery.to_png().as_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_recover_trailing() {
+        let bytes = [testing_bytes().as_slice(), b"hidden"].concat();
+        let recovery = recover(&bytes, false);
+        assert_eq!(recovery.trailing, b"hidden");
+        assert!(recovery.failures.is_empty());
+    }
+
+    #[test]
+    fn test_recover_truncated() {
+        let bytes = testing_bytes();
+        let recovery = recover(&bytes[..bytes.len() - 14], false);
+        assert_eq!(types(&recovery), ["IHDR", "tEXt"]);
+        assert_eq!(
+            recovery.failures[0].reason,
+            FailureReason::Truncated {
+                needed: 16,
+                available: 14
+            }
+        );
+        assert_eq!(recovery.failures[0].skipped, 14);
+    }
+
+    #[test]
+    fn test_recover_bad_crc() {
+        let mut bytes = testing_bytes();
+        // Flip a byte of the tEXt data.
+        bytes[8 + 25 + 8] ^= 0xff;
+
+        let recovery = recover(&bytes, false);
+        assert_eq!(types(&recovery), ["IHDR"]);
+        assert_eq!(recovery.failures[0].offset, 33);
+        assert!(matches!(
+            recovery.failures[0].reason,
+            FailureReason::BadCrc { .. }
+        ));
+
+        let recovery = recover(&bytes, true);
+        assert_eq!(types(&recovery), ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(recovery.failures[0].skipped, 12 + 13);
+    }
+
+    #[test]
+    fn test_recover_bad_type_with_resync() {
+        let mut bytes = testing_bytes();
+        bytes[33 + 4..33 + 8].copy_from_slice(b"t3Xt");
+        // Garbage between two chunks.
+        bytes.splice(33..33, [0xde, 0xad, 0xbe, 0xef]);
+
+        let recovery = recover(&bytes, true);
+        assert_eq!(types(&recovery), ["IHDR", "IDAT", "IEND"]);
+        assert_eq!(recovery.failures.len(), 1);
+        assert_eq!(recovery.failures[0].skipped, 4 + 12 + 13);
+    }
+
+    #[test]
+    fn test_resync_is_bounded() {
+        let bytes = testing_bytes();
+        let candidate = [(1u32 << 20).to_be_bytes().as_slice(), b"ruSt"].concat();
+        let damaged = [
+            &bytes[..33],
+            &[0xff; 4],
+            &candidate.repeat(RESYNC_BUDGET >> 20),
+            &vec![0; 1 << 20],
+            &bytes[33..],
+        ]
+        .concat();
+
+        // Every candidate claims 1 MiB, the budget runs out before tEXt is reached.
+        let recovery = recover(&damaged, true);
+        assert_eq!(types(&recovery), ["IHDR"]);
+        assert_eq!(recovery.failures[0].skipped, damaged.len() - 33);
+
+        let damaged = [
+            &bytes[..33],
+            &[0xff; 4],
+            &candidate,
+            &vec![0; 1 << 20],
+            &bytes[33..],
+        ]
+        .concat();
+        let recovery = recover(&damaged, true);
+        assert_eq!(types(&recovery), ["IHDR", "tEXt", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_resync_finds_chunk_larger_than_budget() {
+        let bytes = testing_bytes();
+        let large = Chunk::new(
+            ChunkType::from_str("IDAT").unwrap(),
+            vec![0; RESYNC_BUDGET + 1],
+        );
+        let damaged = [&bytes[..33], &[0xff; 4], &large.as_bytes(), &bytes[33..]].concat();
+
+        let recovery = recover(&damaged, true);
+        assert_eq!(types(&recovery), ["IHDR", "IDAT", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(recovery.failures[0].skipped, 4);
+    }
+
+    #[test]
+    fn test_recover_bad_signature() {
+        let mut bytes = testing_bytes();
+        bytes[0] = 0;
+
+        let recovery = recover(&bytes, false);
+        assert!(!recovery.signature_valid);
+        assert_eq!(recovery.chunks.len(), 4);
+    }
+
+    #[test]
+    fn test_recover_bad_length() {
+        let mut bytes = testing_bytes();
+        bytes[33..37].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        let recovery = recover(&bytes, false);
+        assert_eq!(
+            recovery.failures[0].reason,
+            FailureReason::BadLength(u32::MAX)
+        );
+    }
+}
@@ -0,0 +1,350 @@
+use crate::{
+    chunk::{calc_crc, Chunk},
+    chunk_type::ChunkType,
+    error::Error,
+    png::Png,
+    recover::RESYNC_BUDGET,
+};
+use std::str::FromStr;
+
+/// length + type + CRC
+const FRAME_LEN: usize = 12;
+
+/// One fix made by [`repair`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The file did not start with the PNG signature.
+    SignatureFixed,
+    /// The stored CRC was replaced with the computed one.
+    CrcFixed {
+        index: usize,
+        chunk_type: String,
+        stored: u32,
+        computed: u32,
+    },
+    /// The length field did not match where the chunk actually ends.
+    LengthFixed {
+        index: usize,
+        chunk_type: String,
+        stored: u32,
+        actual: u32,
+    },
+    /// `IEND` was missing and appended.
+    IendAdded { index: usize },
+    /// Bytes that could not be made into chunks.
+    Dropped {
+        offset: usize,
+        length: usize,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SignatureFixed => write!(f, "Signature fixed"),
+            Self::CrcFixed {
+                index,
+                chunk_type,
+                stored,
+                computed,
+            } => write!(
+                f,
+                "[chunk {}] {} CRC fixed, {:#010x} -> {:#010x}",
+                index, chunk_type, stored, computed
+            ),
+            Self::LengthFixed {
+                index,
+                chunk_type,
+                stored,
+                actual,
+            } => write!(
+                f,
+                "[chunk {}] {} length fixed, {} -> {}",
+                index, chunk_type, stored, actual
+            ),
+            Self::IendAdded { index } => write!(f, "[chunk {}] IEND added", index),
+            Self::Dropped {
+                offset,
+                length,
+                reason,
+            } => write!(
+                f,
+                "{} byte(s) at offset {} dropped, {}",
+                length, offset, reason
+            ),
+        }
+    }
+}
+
+/// A repaired PNG and what was changed to get it.
+pub struct Repaired {
+    pub png: Png,
+    pub changes: Vec<Change>,
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+/// Type of the chunk whose header starts at `at`, if it is a valid one.
+fn type_at(bytes: &[u8], at: usize) -> Option<ChunkType> {
+    if at + 8 > bytes.len() {
+        return None;
+    }
+
+    ChunkType::try_from(TryInto::<[u8; 4]>::try_into(&bytes[at + 4..at + 8]).unwrap()).ok()
+}
+
+/// Whether a chunk ending at `end` is followed by the end of data or another chunk header.
+fn is_boundary(bytes: &[u8], end: usize) -> bool {
+    end == bytes.len() || type_at(bytes, end).is_some()
+}
+
+/// Whether the chunk at `offset` holds together with `length` bytes of data.
+fn crc_matches(bytes: &[u8], offset: usize, length: usize) -> bool {
+    // Type and data sit next to each other, so a candidate is checked without copying it.
+    calc_crc(&bytes[offset + 4..offset + 8 + length]) == read_u32(bytes, offset + 8 + length)
+}
+
+/// The length up to `max_length` that lands the chunk at `offset` on a boundary with its
+/// stored CRC matching, `None` if there is none or the lengths that failed the CRC check add
+/// up to [`RESYNC_BUDGET`].
+fn find_length(bytes: &[u8], offset: usize, max_length: usize) -> Option<usize> {
+    let mut budget = RESYNC_BUDGET;
+
+    for length in 0..=max_length {
+        if !is_boundary(bytes, offset + FRAME_LEN + length) {
+            continue;
+        }
+
+        if crc_matches(bytes, offset, length) {
+            return Some(length);
+        }
+
+        budget = budget.checked_sub(FRAME_LEN + length)?;
+    }
+
+    None
+}
+
+/// Load `bytes` with the CRC check relaxed and fix what the structure makes clear.
+///
+/// A wrong length is only corrected when another length makes the stored CRC match and lands
+/// on a chunk boundary, the search gives up once the lengths it ruled out add up to 64 MiB.
+/// Parsing stops at the first chunk that can not be made sense of, and at
+/// `IEND`.
+pub fn repair(bytes: &[u8]) -> Result<Repaired, Error> {
+    let mut changes = Vec::new();
+    let mut chunks = Vec::new();
+    let mut offset = Png::STANDARD_HEADER.len().min(bytes.len());
+
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        changes.push(Change::SignatureFixed);
+    }
+
+    while offset < bytes.len() {
+        let drop = |reason: &str| Change::Dropped {
+            offset,
+            length: bytes.len() - offset,
+            reason: reason.into(),
+        };
+
+        let chunk_type = match type_at(bytes, offset) {
+            Some(chunk_type) if offset + FRAME_LEN <= bytes.len() => chunk_type,
+            _ => {
+                changes.push(drop("no chunk header found"));
+                break;
+            }
+        };
+        let stored = read_u32(bytes, offset);
+        let max_length = bytes.len() - offset - FRAME_LEN;
+        let holds = |length: usize| {
+            is_boundary(bytes, offset + FRAME_LEN + length) || crc_matches(bytes, offset, length)
+        };
+        let length = if stored as usize <= max_length && holds(stored as usize) {
+            stored as usize
+        } else {
+            match find_length(bytes, offset, max_length) {
+                Some(length) => {
+                    changes.push(Change::LengthFixed {
+                        index: chunks.len(),
+                        chunk_type: chunk_type.to_string(),
+                        stored,
+                        actual: length as u32,
+                    });
+                    length
+                }
+                None => {
+                    changes.push(drop(&format!(
+                        "{} length {} does not fit",
+                        chunk_type, stored
+                    )));
+                    break;
+                }
+            }
+        };
+
+        let chunk = Chunk::new(chunk_type, bytes[offset + 8..offset + 8 + length].to_vec());
+        let stored_crc = read_u32(bytes, offset + 8 + length);
+
+        if chunk.crc() != stored_crc {
+            changes.push(Change::CrcFixed {
+                index: chunks.len(),
+                chunk_type: chunk.chunk_type().to_string(),
+                stored: stored_crc,
+                computed: chunk.crc(),
+            });
+        }
+
+        offset += FRAME_LEN + length;
+
+        let is_iend = chunk.chunk_type().bytes() == *b"IEND";
+
+        chunks.push(chunk);
+
+        if is_iend {
+            if offset < bytes.len() {
+                changes.push(Change::Dropped {
+                    offset,
+                    length: bytes.len() - offset,
+                    reason: "after IEND".into(),
+                });
+            }
+
+            break;
+        }
+    }
+
+    if chunks.is_empty() {
+        return Err(Error::InvalidData("No chunk can be repaired".into()));
+    }
+
+    if chunks.last().map(|c| c.chunk_type().bytes()) != Some(*b"IEND") {
+        changes.push(Change::IendAdded {
+            index: chunks.len(),
+        });
+        chunks.push(Chunk::new(ChunkType::from_str("IEND")?, vec![]));
+    }
+
+    Ok(Repaired {
+        png: Png::from_chunks(chunks),
+        changes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::from_chunks(
+            [
+                ("IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+                ("tEXt", b"Comment\0hello".to_vec()),
+                ("IDAT", vec![1, 2, 3, 4]),
+                ("IEND", vec![]),
+            ]
+            .into_iter()
+            .map(|(t, data)| Chunk::new(ChunkType::from_str(t).unwrap(), data))
+            .collect(),
+        )
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_repair_intact() {
+        let repaired = repair(&testing_bytes()).unwrap();
+        assert!(repaired.changes.is_empty());
+        assert_eq!(repaired.png.as_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_repair_crc() {
+        let mut bytes = testing_bytes();
+        // Flip a byte of the tEXt data.
+        bytes[8 + 25 + 8] ^= 0xff;
+
+        let repaired = repair(&bytes).unwrap();
+        assert_eq!(repaired.changes.len(), 1);
+        assert!(matches!(
+            repaired.changes[0],
+            Change::CrcFixed { index: 1, .. }
+        ));
+        assert_eq!(repaired.png.chunks().len(), 4);
+        assert!(Png::try_from(repaired.png.as_bytes().as_slice()).is_ok());
+    }
+
+    #[test]
+    fn test_repair_length() {
+        let mut bytes = testing_bytes();
+        bytes[33..37].copy_from_slice(&200u32.to_be_bytes());
+
+        let repaired = repair(&bytes).unwrap();
+        assert_eq!(
+            repaired.changes,
+            [Change::LengthFixed {
+                index: 1,
+                chunk_type: "tEXt".into(),
+                stored: 200,
+                actual: 13
+            }]
+        );
+        assert_eq!(repaired.png.as_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_repair_length_search_is_bounded() {
+        let bytes = testing_bytes();
+        let text = Chunk::new(ChunkType::from_str("tEXt").unwrap(), vec![b'A'; 16 * 1024]);
+        let mut damaged = [&bytes[..33], &text.as_bytes(), &bytes[58..]].concat();
+        damaged[33..37].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        // Every offset in the data looks like a boundary, the search gives up long before the
+        // real end of the chunk.
+        let repaired = repair(&damaged).unwrap();
+        assert!(matches!(
+            repaired.changes[0],
+            Change::Dropped { offset: 33, .. }
+        ));
+        assert_eq!(repaired.png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_repair_missing_iend() {
+        let bytes = testing_bytes();
+        let repaired = repair(&bytes[..bytes.len() - 12]).unwrap();
+        assert_eq!(repaired.changes, [Change::IendAdded { index: 3 }]);
+        assert_eq!(repaired.png.as_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_repair_truncated() {
+        let bytes = testing_bytes();
+        let repaired = repair(&bytes[..bytes.len() - 14]).unwrap();
+        assert!(matches!(
+            repaired.changes[0],
+            Change::Dropped { length: 14, .. }
+        ));
+        assert_eq!(repaired.changes[1], Change::IendAdded { index: 2 });
+    }
+
+    #[test]
+    fn test_repair_signature_and_trailing() {
+        let mut bytes = [testing_bytes().as_slice(), b"tail"].concat();
+        bytes[1] = 0;
+
+        let repaired = repair(&bytes).unwrap();
+        assert_eq!(repaired.changes[0], Change::SignatureFixed);
+        assert!(matches!(
+            repaired.changes[1],
+            Change::Dropped { length: 4, .. }
+        ));
+        assert_eq!(repaired.png.as_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_repair_nothing() {
+        assert!(repair(&Png::STANDARD_HEADER).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use crate::error::Error;
+
+pub const FILTER_NONE: u8 = 0;
+pub const FILTER_SUB: u8 = 1;
+pub const FILTER_UP: u8 = 2;
+pub const FILTER_AVERAGE: u8 = 3;
+pub const FILTER_PAETH: u8 = 4;
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Predictor for byte `i` of a row, `prev` is the reconstructed row above.
+fn predict(filter_type: u8, row: &[u8], prev: &[u8], i: usize, bpp: usize) -> u8 {
+    let a = if i >= bpp { row[i - bpp] } else { 0 };
+    let b = prev[i];
+    let c = if i >= bpp { prev[i - bpp] } else { 0 };
+
+    match filter_type {
+        FILTER_SUB => a,
+        FILTER_UP => b,
+        FILTER_AVERAGE => ((a as u16 + b as u16) / 2) as u8,
+        FILTER_PAETH => paeth(a, b, c),
+        _ => 0,
+    }
+}
+
+/// Undo the per-row filters of inflated image data.
+///
+/// Returns the raw rows without their filter bytes, and the filter type of every row.
+pub fn unfilter(data: &[u8], stride: usize, bpp: usize) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    if !data.len().is_multiple_of(stride + 1) {
+        return Err(Error::InvalidData(format!(
+            "Image data of {} byte(s) is not made of {} byte rows",
+            data.len(),
+            stride + 1
+        )));
+    }
+
+    let rows = data.len() / (stride + 1);
+    let mut raw = vec![0u8; rows * stride];
+    let mut filter_types = Vec::with_capacity(rows);
+    let zero_row = vec![0u8; stride];
+
+    for (y, line) in data.chunks(stride + 1).enumerate() {
+        let filter_type = line[0];
+
+        if !(FILTER_NONE..=FILTER_PAETH).contains(&filter_type) {
+            return Err(Error::InvalidData(format!(
+                "Unknown filter type {} at row {}",
+                filter_type, y
+            )));
+        }
+
+        let (done, rest) = raw.split_at_mut(y * stride);
+        let prev = if y == 0 {
+            &zero_row[..]
+        } else {
+            &done[(y - 1) * stride..]
+        };
+        let row = &mut rest[..stride];
+
+        for i in 0..stride {
+            row[i] = line[i + 1].wrapping_add(predict(filter_type, row, prev, i, bpp));
+        }
+
+        filter_types.push(filter_type);
+    }
+
+    Ok((raw, filter_types))
+}
+
+/// Filter one raw row with `filter_type`, `prev` is the raw row above.
+pub fn filter_row(filter_type: u8, row: &[u8], prev: &[u8], bpp: usize) -> Vec<u8> {
+    let mut line = Vec::with_capacity(row.len() + 1);
+
+    line.push(filter_type);
+    line.extend(
+        (0..row.len()).map(|i| row[i].wrapping_sub(predict(filter_type, row, prev, i, bpp))),
+    );
+
+    line
+}
+
+/// Filter raw rows, row `y` with `filter_types[y]`.
+pub fn filter(raw: &[u8], stride: usize, bpp: usize, filter_types: &[u8]) -> Vec<u8> {
+    let zero_row = vec![0u8; stride];
+
+    raw.chunks(stride)
+        .enumerate()
+        .flat_map(|(y, row)| {
+            let prev = if y == 0 {
+                &zero_row[..]
+            } else {
+                &raw[(y - 1) * stride..y * stride]
+            };
+
+            filter_row(filter_types[y], row, prev, bpp)
+        })
+        .collect()
+}
+
+/// Filter raw rows, each with the type whose filtered row costs least, ties going to the lower
+/// type. `cost` is given the filtered row without its filter byte.
+pub fn filter_cheapest<C>(raw: &[u8], stride: usize, bpp: usize, cost: C) -> Vec<u8>
+where
+    C: Fn(&[u8]) -> f64,
+{
+    let zero_row = vec![0u8; stride];
+
+    raw.chunks(stride)
+        .enumerate()
+        .flat_map(|(y, row)| {
+            let prev = if y == 0 {
+                &zero_row[..]
+            } else {
+                &raw[(y - 1) * stride..y * stride]
+            };
+
+            (FILTER_NONE..=FILTER_PAETH)
+                .map(|filter_type| {
+                    let line = filter_row(filter_type, row, prev, bpp);
+                    (cost(&line[1..]), line)
+                })
+                .min_by(|(a, _), (b, _)| a.total_cmp(b))
+                .unwrap()
+                .1
+        })
+        .collect()
+}
+
+/// Sum of the absolute values of `bytes` taken as signed, the usual heuristic for adaptive
+/// filtering.
+pub fn sum_of_abs(bytes: &[u8]) -> f64 {
+    bytes
+        .iter()
+        .map(|&b| (b as i8).unsigned_abs() as u64)
+        .sum::<u64>() as f64
+}
+
+/// Shannon entropy of `bytes` in bits, about what an ideal coder needs for them on their own.
+pub fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+
+    for &b in bytes {
+        counts[b as usize] += 1;
+    }
+
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let count = count as f64;
+            count * (bytes.len() as f64 / count).log2()
+        })
+        .sum()
+}
+
+/// Filter raw rows, each with the type whose output has the smallest [`sum_of_abs`].
+pub fn filter_min_sum(raw: &[u8], stride: usize, bpp: usize) -> Vec<u8> {
+    filter_cheapest(raw, stride, bpp, sum_of_abs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_raw() -> Vec<u8> {
+        (0..48u32).map(|i| (i * 37 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_filter_round_trip() {
+        let raw = testing_raw();
+
+        for filter_type in FILTER_NONE..=FILTER_PAETH {
+            let filter_types = vec![filter_type; 4];
+            let filtered = filter(&raw, 12, 3, &filter_types);
+            assert_eq!(filtered.len(), 4 * 13);
+
+            let (actual, actual_types) = unfilter(&filtered, 12, 3).unwrap();
+            assert_eq!(actual, raw);
+            assert_eq!(actual_types, filter_types);
+        }
+    }
+
+    #[test]
+    fn test_mixed_filters_round_trip() {
+        let raw = testing_raw();
+        let filter_types = [FILTER_PAETH, FILTER_SUB, FILTER_AVERAGE, FILTER_UP];
+        let filtered = filter(&raw, 12, 3, &filter_types);
+        assert_eq!(unfilter(&filtered, 12, 3).unwrap().0, raw);
+    }
+
+    #[test]
+    fn test_filter_min_sum() {
+        // A gradient along the row is cheapest with Sub, equal rows with Up.
+        let raw: Vec<u8> = (0..12).map(|i| i * 10).collect::<Vec<u8>>().repeat(2);
+        let filtered = filter_min_sum(&raw, 12, 1);
+
+        assert_eq!(filtered[0], FILTER_SUB);
+        assert_eq!(filtered[13], FILTER_UP);
+        assert_eq!(unfilter(&filtered, 12, 1).unwrap().0, raw);
+
+        assert_eq!(
+            filter_min_sum(&[0; 8], 4, 1),
+            [FILTER_NONE, 0, 0, 0, 0].repeat(2)
+        );
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[]), 0.0);
+        assert_eq!(entropy(&[7; 16]), 0.0);
+        assert_eq!(entropy(&[0, 1, 2, 3]), 8.0);
+        assert_eq!(sum_of_abs(&[1, 255, 128]), 130.0);
+    }
+
+    #[test]
+    fn test_unfilter_invalid() {
+        assert!(unfilter(&[0, 1, 2], 3, 1).is_err());
+        assert!(unfilter(&[5, 1, 2], 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_paeth() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::error::Error;
+use crc::{Crc, CRC_32_ISO_HDLC};
+use std::collections::BTreeMap;
+
+const VERSION: u8 = 1;
+/// version + payload id + index + total + checksum
+pub(crate) const HEADER_LEN: usize = 1 + 4 + 4 + 4 + 4;
+
+fn checksum(bytes: &[u8]) -> u32 {
+    Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(bytes)
+}
+
+/// One numbered piece of a payload that was split across several chunks.
+///
+/// Laid out as `version | payload id | index | total | checksum | data`, all numbers are big
+/// endian `u32` and `checksum` is the CRC-32 of the whole payload. That a chunk holds a piece
+/// at all is recorded in its [`Header`](crate::payload::Header).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piece {
+    pub payload_id: u32,
+    pub index: u32,
+    pub total: u32,
+    pub checksum: u32,
+    pub data: Vec<u8>,
+}
+
+/// A payload put back together from its pieces.
+#[derive(Debug)]
+pub struct Reassembled {
+    pub data: Vec<u8>,
+    /// Indices of pieces that were found more than once with the same data.
+    pub duplicated: Vec<u32>,
+}
+
+impl TryFrom<&[u8]> for Piece {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() < HEADER_LEN {
+            return Err(Error::InvalidData("Payload piece is truncated".into()));
+        }
+
+        if value[0] != VERSION {
+            return Err(Error::UnsupportedVersion {
+                what: "payload piece",
+                version: value[0],
+            });
+        }
+
+        let read_u32 = |at: usize| u32::from_be_bytes(value[at..at + 4].try_into().unwrap());
+
+        Ok(Self {
+            payload_id: read_u32(1),
+            index: read_u32(5),
+            total: read_u32(9),
+            checksum: read_u32(13),
+            data: value[HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+impl Piece {
+    /// Split `data` into pieces holding at most `piece_size` bytes each.
+    pub fn split(data: &[u8], piece_size: usize) -> Result<Vec<Piece>, Error> {
+        if piece_size == 0 {
+            return Err(Error::InvalidInput(
+                "Piece size must be greater than 0".into(),
+            ));
+        }
+
+        let checksum = checksum(data);
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let payload_id = checksum ^ nanos;
+        let parts: Vec<&[u8]> = if data.is_empty() {
+            vec![data]
+        } else {
+            data.chunks(piece_size).collect()
+        };
+        let total = u32::try_from(parts.len())
+            .map_err(|_| Error::InvalidInput("Too many pieces".into()))?;
+
+        Ok(parts
+            .into_iter()
+            .enumerate()
+            .map(|(index, part)| Piece {
+                payload_id,
+                index: index as u32,
+                total,
+                checksum,
+                data: part.to_vec(),
+            })
+            .collect())
+    }
+
+    /// Put pieces of the same payload back together, in any order.
+    pub fn reassemble(pieces: &[Piece]) -> Result<Reassembled, Error> {
+        let first = pieces
+            .first()
+            .ok_or_else(|| Error::NotFound("No payload piece found".into()))?;
+
+        let mut slots: BTreeMap<u32, &Piece> = BTreeMap::new();
+        let mut duplicated = Vec::new();
+
+        for piece in pieces.iter().filter(|p| p.payload_id == first.payload_id) {
+            if piece.total != first.total || piece.checksum != first.checksum {
+                return Err(Error::InvalidData(format!(
+                    "Piece {} disagrees with the payload header",
+                    piece.index
+                )));
+            }
+
+            if piece.index >= piece.total {
+                return Err(Error::InvalidData(format!(
+                    "Piece {} is out of range of {}",
+                    piece.index, piece.total
+                )));
+            }
+
+            match slots.get(&piece.index) {
+                Some(existing) if existing.data != piece.data => {
+                    return Err(Error::InvalidData(format!(
+                        "Piece {} is duplicated with different data",
+                        piece.index
+                    )))
+                }
+                Some(_) => duplicated.push(piece.index),
+                None => {
+                    slots.insert(piece.index, piece);
+                }
+            }
+        }
+
+        let missing: Vec<String> = (0..first.total)
+            .filter(|idx| !slots.contains_key(idx))
+            .map(|idx| idx.to_string())
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(Error::NotFound(format!(
+                "Missing {} of {} piece(s): {}",
+                missing.len(),
+                first.total,
+                missing.join(", ")
+            )));
+        }
+
+        let data: Vec<u8> = slots.values().flat_map(|p| p.data.clone()).collect();
+
+        if checksum(&data) != first.checksum {
+            return Err(Error::InvalidData(
+                "Reassembled payload checksum mismatch".into(),
+            ));
+        }
+
+        Ok(Reassembled { data, duplicated })
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            [VERSION].as_slice(),
+            &self.payload_id.to_be_bytes(),
+            &self.index.to_be_bytes(),
+            &self.total.to_be_bytes(),
+            &self.checksum.to_be_bytes(),
+            &self.data,
+        ]
+        .concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"This is where your secret message will be!";
+
+    #[test]
+    fn test_split() {
+        let pieces = Piece::split(PAYLOAD, 10).unwrap();
+        assert_eq!(pieces.len(), 5);
+        assert!(pieces.iter().all(|p| p.total == 5));
+        assert_eq!(pieces[4].data, b"e!");
+        assert!(Piece::split(PAYLOAD, 0).is_err());
+    }
+
+    #[test]
+    fn test_piece_round_trip() {
+        let piece = Piece::split(PAYLOAD, 10).unwrap().remove(1);
+        let bytes = piece.as_bytes();
+        assert_eq!(Piece::try_from(bytes.as_slice()).unwrap(), piece);
+    }
+
+    #[test]
+    fn test_invalid_piece() {
+        assert!(matches!(
+            Piece::try_from(b"short".as_slice()),
+            Err(Error::InvalidData(_))
+        ));
+
+        let mut bytes = Piece::split(PAYLOAD, 10).unwrap()[0].as_bytes();
+        bytes[0] = 9;
+        assert!(matches!(
+            Piece::try_from(bytes.as_slice()),
+            Err(Error::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reassemble_out_of_order() {
+        let mut pieces = Piece::split(PAYLOAD, 10).unwrap();
+        pieces.reverse();
+        let reassembled = Piece::reassemble(&pieces).unwrap();
+        assert_eq!(reassembled.data, PAYLOAD);
+        assert!(reassembled.duplicated.is_empty());
+    }
+
+    #[test]
+    fn test_reassemble_duplicated() {
+        let mut pieces = Piece::split(PAYLOAD, 10).unwrap();
+        pieces.push(pieces[2].clone());
+        let reassembled = Piece::reassemble(&pieces).unwrap();
+        assert_eq!(reassembled.data, PAYLOAD);
+        assert_eq!(reassembled.duplicated, vec![2]);
+
+        pieces[5].data = b"conflict".to_vec();
+        assert!(Piece::reassemble(&pieces).is_err());
+    }
+
+    #[test]
+    fn test_reassemble_missing() {
+        let mut pieces = Piece::split(PAYLOAD, 10).unwrap();
+        pieces.remove(3);
+        pieces.remove(1);
+        let err = Piece::reassemble(&pieces).err().unwrap();
+        assert!(matches!(err, Error::NotFound(_)));
+        assert!(err.to_string().ends_with("1, 3"));
+    }
+
+    #[test]
+    fn test_reassemble_checksum_mismatch() {
+        let mut pieces = Piece::split(PAYLOAD, 10).unwrap();
+        pieces[0].data[0] ^= 1;
+        assert!(Piece::reassemble(&pieces).is_err());
+    }
+}
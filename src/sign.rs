@@ -0,0 +1,399 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::Error,
+    hex::{from_hex, to_hex},
+    png::{ChunkPosition, Png},
+};
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+/// Private, ancillary and unsafe to copy, since it depends on the critical chunks.
+pub const SIGNATURE_CHUNK_TYPE: &str = "siGN";
+
+const VERSION: u8 = 1;
+/// Ed25519 over the SHA-256 digest of the covered chunks.
+const ALG_ED25519_SHA256: u8 = 1;
+pub const KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+
+/// Which chunks a signature covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Coverage {
+    /// Every critical chunk, see [`ChunkType::is_critical`].
+    Critical,
+    /// Every chunk of the listed types.
+    Types(Vec<ChunkType>),
+}
+
+impl Coverage {
+    pub(crate) fn covers(&self, chunk_type: &ChunkType) -> bool {
+        if chunk_type.to_string() == SIGNATURE_CHUNK_TYPE {
+            return false;
+        }
+
+        match self {
+            Self::Critical => chunk_type.is_critical(),
+            Self::Types(types) => types.iter().any(|t| t.bytes() == chunk_type.bytes()),
+        }
+    }
+
+    /// Indices of the chunks of `png` covered.
+    pub fn covered(&self, png: &Png) -> Vec<usize> {
+        png.chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| self.covers(chunk.chunk_type()))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Critical => write!(f, "critical chunks"),
+            Self::Types(types) => {
+                let types: Vec<String> = types.iter().map(ChunkType::to_string).collect();
+                write!(f, "{}", types.join(", "))
+            }
+        }
+    }
+}
+
+/// The content of a signature chunk.
+///
+/// Laid out as `version | algorithm | public key | coverage | signature`, where coverage is
+/// `0` for critical chunks or `1 | count | types`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub public_key: [u8; KEY_LEN],
+    pub coverage: Coverage,
+    signature: [u8; SIGNATURE_LEN],
+}
+
+impl Signature {
+    /// Everything but the signature, which is signed along with the chunks.
+    fn signed_header(public_key: &[u8; KEY_LEN], coverage: &Coverage) -> Vec<u8> {
+        let mut bytes = vec![VERSION, ALG_ED25519_SHA256];
+
+        bytes.extend_from_slice(public_key);
+
+        match coverage {
+            Coverage::Critical => bytes.push(0),
+            Coverage::Types(types) => {
+                bytes.extend_from_slice(&[1, types.len() as u8]);
+                types
+                    .iter()
+                    .for_each(|t| bytes.extend_from_slice(&t.bytes()));
+            }
+        }
+
+        bytes
+    }
+
+    fn digest(public_key: &[u8; KEY_LEN], coverage: &Coverage, png: &Png) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+
+        hasher.update(Self::signed_header(public_key, coverage));
+
+        for idx in coverage.covered(png) {
+            hasher.update(png.chunks()[idx].as_bytes());
+        }
+
+        hasher.finalize().into()
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        [
+            Self::signed_header(&self.public_key, &self.coverage).as_slice(),
+            &self.signature,
+        ]
+        .concat()
+    }
+}
+
+impl TryFrom<&[u8]> for Signature {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let invalid = || Error::InvalidData("Signature chunk is truncated".into());
+
+        if value.len() < 2 + KEY_LEN + 1 + SIGNATURE_LEN {
+            return Err(invalid());
+        }
+
+        if value[0] != VERSION {
+            return Err(Error::UnsupportedVersion {
+                what: "signature",
+                version: value[0],
+            });
+        }
+
+        if value[1] != ALG_ED25519_SHA256 {
+            return Err(Error::Unsupported(format!(
+                "Unsupported signature algorithm: {}",
+                value[1]
+            )));
+        }
+
+        let public_key = value[2..2 + KEY_LEN].try_into().unwrap();
+        let mut cursor = 2 + KEY_LEN;
+        let coverage = match value[cursor] {
+            0 => {
+                cursor += 1;
+                Coverage::Critical
+            }
+            1 => {
+                let count = *value.get(cursor + 1).ok_or_else(invalid)? as usize;
+                let types = value
+                    .get(cursor + 2..cursor + 2 + 4 * count)
+                    .ok_or_else(invalid)?
+                    .chunks(4)
+                    .map(|t| ChunkType::try_from(<[u8; 4]>::try_from(t).unwrap()))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                cursor += 2 + 4 * count;
+                Coverage::Types(types)
+            }
+            other => {
+                return Err(Error::InvalidData(format!(
+                    "Unknown signature coverage: {}",
+                    other
+                )))
+            }
+        };
+        let signature = value
+            .get(cursor..)
+            .filter(|rest| rest.len() == SIGNATURE_LEN)
+            .ok_or_else(invalid)?
+            .try_into()
+            .unwrap();
+
+        Ok(Signature {
+            public_key,
+            coverage,
+            signature,
+        })
+    }
+}
+
+/// A new random secret key.
+pub fn generate_key() -> [u8; KEY_LEN] {
+    SigningKey::generate(&mut OsRng).to_bytes()
+}
+
+/// The public key that checks signatures made with `secret_key`.
+pub fn public_key(secret_key: &[u8; KEY_LEN]) -> [u8; KEY_LEN] {
+    SigningKey::from_bytes(secret_key)
+        .verifying_key()
+        .to_bytes()
+}
+
+/// A key as 64 hex digits, the form [`parse_key`] reads back.
+pub fn format_key(key: &[u8; KEY_LEN]) -> String {
+    to_hex(key)
+}
+
+/// Read a key file, either 32 raw bytes or 64 hex digits.
+pub fn parse_key(bytes: &[u8]) -> Result<[u8; KEY_LEN], Error> {
+    if let Ok(key) = <[u8; KEY_LEN]>::try_from(bytes) {
+        return Ok(key);
+    }
+
+    let invalid = || Error::InvalidInput("Key needs 32 bytes or 64 hex digits".into());
+    let hex = std::str::from_utf8(bytes).map_err(|_| invalid())?.trim();
+
+    from_hex(hex)
+        .and_then(|key| key.try_into().ok())
+        .ok_or_else(invalid)
+}
+
+/// Sign the chunks of `png` selected by `coverage`, replacing any earlier signature.
+///
+/// The signature chunk goes right before `IEND`. Returns the indices of the covered chunks.
+pub fn sign(
+    png: &mut Png,
+    secret_key: &[u8; KEY_LEN],
+    coverage: Coverage,
+) -> Result<Vec<usize>, Error> {
+    if matches!(&coverage, Coverage::Types(types) if types.is_empty() || types.len() > 255) {
+        return Err(Error::InvalidInput(
+            "A signature covers between 1 and 255 chunk types".into(),
+        ));
+    }
+
+    let _ = png.remove_chunks(SIGNATURE_CHUNK_TYPE);
+
+    let signing_key = SigningKey::from_bytes(secret_key);
+    let public_key = signing_key.verifying_key().to_bytes();
+    let digest = Signature::digest(&public_key, &coverage, png);
+    let signature = Signature {
+        public_key,
+        signature: signing_key.sign(&digest).to_bytes(),
+        coverage,
+    };
+    let chunk_type = ChunkType::from_str(SIGNATURE_CHUNK_TYPE)?;
+
+    png.insert_chunk(
+        Chunk::new(chunk_type, signature.as_bytes()),
+        ChunkPosition::BeforeIend,
+    )?;
+
+    Ok(signature.coverage.covered(png))
+}
+
+/// A signature that checked out.
+#[derive(Debug)]
+pub struct Verified {
+    pub signature: Signature,
+    /// Indices of the covered chunks.
+    pub covered: Vec<usize>,
+}
+
+/// Check the signature of `png` against `public_key`.
+pub fn verify(png: &Png, public_key: &[u8; KEY_LEN]) -> Result<Verified, Error> {
+    let chunks = png.chunks_by_type(SIGNATURE_CHUNK_TYPE);
+    let signature = match chunks.as_slice() {
+        [chunk] => Signature::try_from(chunk.data())?,
+        [] => {
+            return Err(Error::NotFound(format!(
+                "No {} chunk, the file is not signed",
+                SIGNATURE_CHUNK_TYPE
+            )))
+        }
+        _ => {
+            return Err(Error::InvalidData(format!(
+                "{} {} chunks, expect one",
+                chunks.len(),
+                SIGNATURE_CHUNK_TYPE
+            )))
+        }
+    };
+
+    if signature.public_key != *public_key {
+        return Err(Error::WrongKey(signature.public_key));
+    }
+
+    let verifying_key =
+        VerifyingKey::from_bytes(public_key).map_err(|err| Error::InvalidInput(err.to_string()))?;
+    let digest = Signature::digest(public_key, &signature.coverage, png);
+
+    verifying_key
+        .verify(
+            &digest,
+            &ed25519_dalek::Signature::from_bytes(&signature.signature),
+        )
+        .map_err(|_| Error::BadSignature)?;
+
+    Ok(Verified {
+        covered: signature.coverage.covered(png),
+        signature,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(
+            [
+                ("IHDR", vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+                ("tEXt", b"Comment\0hello".to_vec()),
+                ("IDAT", vec![1, 2, 3, 4]),
+                ("IEND", vec![]),
+            ]
+            .into_iter()
+            .map(|(t, data)| Chunk::new(ChunkType::from_str(t).unwrap(), data))
+            .collect(),
+        )
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let key = generate_key();
+        let mut png = testing_png();
+
+        assert_eq!(sign(&mut png, &key, Coverage::Critical).unwrap(), [0, 2, 4]);
+        assert_eq!(png.chunks()[3].chunk_type().to_string(), "siGN");
+
+        let verified = verify(&png, &public_key(&key)).unwrap();
+        assert_eq!(verified.covered, [0, 2, 4]);
+        assert_eq!(verified.signature.coverage, Coverage::Critical);
+
+        // Ancillary chunks are not covered by default.
+        png.set_chunk_data("tEXt", 0, b"Comment\0changed".to_vec())
+            .unwrap();
+        assert!(verify(&png, &public_key(&key)).is_ok());
+
+        png.set_chunk_data("IDAT", 0, vec![4, 3, 2, 1]).unwrap();
+        assert!(matches!(
+            verify(&png, &public_key(&key)),
+            Err(Error::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn test_sign_listed_types() {
+        let key = generate_key();
+        let mut png = testing_png();
+        let coverage = Coverage::Types(vec![ChunkType::from_str("tEXt").unwrap()]);
+
+        assert_eq!(sign(&mut png, &key, coverage.clone()).unwrap(), [1]);
+
+        // Signing again replaces the signature.
+        sign(&mut png, &key, coverage).unwrap();
+        assert_eq!(png.chunks_by_type("siGN").len(), 1);
+
+        png.set_chunk_data("tEXt", 0, b"Comment\0changed".to_vec())
+            .unwrap();
+        assert!(verify(&png, &public_key(&key)).is_err());
+    }
+
+    #[test]
+    fn test_verify_other_key() {
+        let mut png = testing_png();
+
+        assert!(matches!(
+            verify(&png, &[0; KEY_LEN]),
+            Err(Error::NotFound(_))
+        ));
+
+        sign(&mut png, &generate_key(), Coverage::Critical).unwrap();
+        let other = generate_key();
+        let err = verify(&png, &public_key(&other)).unwrap_err();
+        assert!(matches!(err, Error::WrongKey(key) if key != public_key(&other)));
+        assert!(err.to_string().starts_with("Signed by another key"));
+    }
+
+    #[test]
+    fn test_signature_round_trip() {
+        let signature = Signature {
+            public_key: [7; KEY_LEN],
+            coverage: Coverage::Types(vec![
+                ChunkType::from_str("IHDR").unwrap(),
+                ChunkType::from_str("tIME").unwrap(),
+            ]),
+            signature: [9; SIGNATURE_LEN],
+        };
+        let bytes = signature.as_bytes();
+
+        assert_eq!(Signature::try_from(bytes.as_slice()).unwrap(), signature);
+        assert!(Signature::try_from(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_parse_key() {
+        let key = generate_key();
+
+        assert_eq!(parse_key(&key).unwrap(), key);
+        assert_eq!(
+            parse_key(format!("{}\n", format_key(&key)).as_bytes()).unwrap(),
+            key
+        );
+        assert!(parse_key(b"not a key").is_err());
+    }
+}
@@ -0,0 +1,512 @@
+use crate::{
+    apng::FrameTracker,
+    chunk::{Chunk, MAX_LENGTH},
+    chunk_type::ChunkType,
+    error::Error,
+    png::{self, ChunkPosition, Png},
+};
+use crc::{Crc, CRC_32_ISO_HDLC};
+use std::io::{ErrorKind, Read, Write};
+
+static CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+const BUFFER_LEN: usize = 64 * 1024;
+
+/// Length and type of a chunk whose data has not been read yet.
+#[derive(Debug, Clone)]
+pub struct ChunkHeader {
+    pub length: u32,
+    pub chunk_type: ChunkType,
+}
+
+/// Fill `buf`, returning `false` when the reader is already at its end.
+fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool, Error> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => {
+                return Err(Error::Truncated {
+                    needed: buf.len(),
+                    available: filled,
+                })
+            }
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Reads a PNG chunk by chunk, so chunk data is only held in memory when asked for.
+///
+/// Errors inside a chunk carry its index and byte offset.
+pub struct PngReader<R: Read> {
+    reader: R,
+    pending: Option<ChunkHeader>,
+    /// Index of the next chunk.
+    index: usize,
+    /// Bytes read so far.
+    offset: usize,
+}
+
+impl<R: Read> PngReader<R> {
+    /// Read and check the PNG signature.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut header = [0u8; 8];
+
+        reader.read_exact(&mut header)?;
+
+        if header != Png::STANDARD_HEADER {
+            return Err(Error::InvalidSignature(header));
+        }
+
+        Ok(Self {
+            reader,
+            pending: None,
+            index: 0,
+            offset: header.len(),
+        })
+    }
+
+    /// Read the header of the next chunk, skipping the current one if it was not consumed.
+    pub fn next_header(&mut self) -> Result<Option<ChunkHeader>, Error> {
+        if self.pending.is_some() {
+            self.skip_chunk()?;
+        }
+
+        let mut buf = [0u8; 8];
+
+        let header = read_exact_or_eof(&mut self.reader, &mut buf)
+            .and_then(|read| {
+                if !read {
+                    return Ok(None);
+                }
+
+                let length = u32::from_be_bytes(buf[..4].try_into().unwrap());
+
+                if length > MAX_LENGTH {
+                    return Err(Error::InvalidData(format!(
+                        "Chunk length {} exceeds the PNG limit of {}",
+                        length, MAX_LENGTH
+                    )));
+                }
+
+                Ok(Some(ChunkHeader {
+                    length,
+                    chunk_type: ChunkType::try_from(
+                        TryInto::<[u8; 4]>::try_into(&buf[4..]).unwrap(),
+                    )?,
+                }))
+            })
+            .map_err(|err| err.in_chunk(self.index, self.offset))?;
+
+        self.pending.clone_from(&header);
+
+        Ok(header)
+    }
+
+    /// Read the whole current chunk into memory.
+    ///
+    /// The buffer grows as data arrives, so a length field larger than the file costs no more
+    /// memory than the file.
+    pub fn read_chunk(&mut self) -> Result<Chunk, Error> {
+        let header = self.take_pending()?;
+        let length = header.length as usize;
+        let mut data = Vec::with_capacity(length.min(BUFFER_LEN));
+        let mut crc = [0u8; 4];
+
+        let chunk = (&mut self.reader)
+            .take(header.length as u64)
+            .read_to_end(&mut data)
+            .map_err(Error::from)
+            .and_then(|read| match read == length {
+                true => Ok(self.reader.read_exact(&mut crc)?),
+                false => Err(Error::Truncated {
+                    needed: length,
+                    available: read,
+                }),
+            })
+            .and_then(|_| Chunk::from_parts(header.chunk_type, data, u32::from_be_bytes(crc)));
+
+        self.finish_chunk(header.length, chunk)
+    }
+
+    /// Stream the current chunk to `writer` without holding its data in memory.
+    pub fn copy_chunk<W: Write>(&mut self, writer: &mut PngWriter<W>) -> Result<(), Error> {
+        let header = self.take_pending()?;
+
+        writer.writer.write_all(&header.length.to_be_bytes())?;
+        writer.writer.write_all(&header.chunk_type.bytes())?;
+
+        let crc = self.stream_data(&header, Some(&mut writer.writer));
+        let crc = self.finish_chunk(header.length, crc)?;
+
+        writer.writer.write_all(&crc.to_be_bytes())?;
+
+        Ok(())
+    }
+
+    /// Consume the current chunk, only checking its CRC.
+    pub fn skip_chunk(&mut self) -> Result<(), Error> {
+        let header = self.take_pending()?;
+        let crc = self.stream_data::<std::io::Sink>(&header, None);
+
+        self.finish_chunk(header.length, crc).map(|_| ())
+    }
+
+    fn take_pending(&mut self) -> Result<ChunkHeader, Error> {
+        self.pending
+            .take()
+            .ok_or_else(|| Error::InvalidInput("No chunk header was read".into()))
+    }
+
+    /// Move past the current chunk, attaching its index and offset to `res` if it failed.
+    fn finish_chunk<T>(&mut self, length: u32, res: Result<T, Error>) -> Result<T, Error> {
+        let res = res.map_err(|err| err.in_chunk(self.index, self.offset));
+
+        self.index += 1;
+        self.offset += 12 + length as usize;
+
+        res
+    }
+
+    fn stream_data<W: Write>(
+        &mut self,
+        header: &ChunkHeader,
+        mut writer: Option<&mut W>,
+    ) -> Result<u32, Error> {
+        let mut digest = CRC32.digest();
+        let mut buf = vec![0u8; BUFFER_LEN.min(header.length as usize)];
+        let mut remaining = header.length as usize;
+
+        digest.update(&header.chunk_type.bytes());
+
+        while remaining > 0 {
+            let len = remaining.min(buf.len());
+
+            self.reader.read_exact(&mut buf[..len])?;
+            digest.update(&buf[..len]);
+
+            if let Some(writer) = writer.as_mut() {
+                writer.write_all(&buf[..len])?;
+            }
+
+            remaining -= len;
+        }
+
+        let mut crc = [0u8; 4];
+
+        self.reader.read_exact(&mut crc)?;
+
+        let stored = u32::from_be_bytes(crc);
+        let computed = digest.finalize();
+
+        if computed != stored {
+            return Err(Error::CrcMismatch { stored, computed });
+        }
+
+        Ok(stored)
+    }
+}
+
+/// Writes a PNG chunk by chunk.
+pub struct PngWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PngWriter<W> {
+    /// Write the PNG signature.
+    pub fn new(mut writer: W) -> Result<Self, Error> {
+        writer.write_all(&Png::STANDARD_HEADER)?;
+
+        Ok(Self { writer })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<(), Error> {
+        self.writer
+            .write_all(&chunk.as_bytes())
+            .map_err(Error::from)
+    }
+
+    /// Flush and give back the underlying writer.
+    pub fn finish(mut self) -> Result<W, Error> {
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+/// Copy every chunk from `reader` to `writer`, placing `chunks` at `position` on the way.
+///
+/// Returns the index the first new chunk was placed at, as [`Png::insert_chunks`] does, which
+/// also keeps APNG frames intact the same way.
+pub fn insert_chunks<R: Read, W: Write>(
+    reader: &mut PngReader<R>,
+    writer: &mut PngWriter<W>,
+    chunks: &[Chunk],
+    position: ChunkPosition,
+) -> Result<usize, Error> {
+    let mut idx = 0;
+    let mut inserted = None;
+    let mut tracker = FrameTracker::default();
+    let mut seen_iend = false;
+    // An fcTL held back until it is known whether the default image follows it.
+    let mut held_fctl: Option<Chunk> = None;
+    let insert = |writer: &mut PngWriter<W>, idx: usize| -> Result<Option<usize>, Error> {
+        for chunk in chunks {
+            writer.write_chunk(chunk)?;
+        }
+
+        Ok(Some(idx))
+    };
+
+    while let Some(header) = reader.next_header()? {
+        let chunk_type = header.chunk_type.to_string();
+
+        if let (None, ChunkPosition::Index(at)) = (inserted, position) {
+            if seen_iend || (chunk_type == "IHDR" && at <= idx) {
+                return Err(png::outside_image(at));
+            }
+        }
+
+        if inserted.is_none()
+            && match position {
+                ChunkPosition::BeforeIend => chunk_type == "IEND",
+                ChunkPosition::BeforeIdat => chunk_type == "IDAT",
+                ChunkPosition::Index(at) => at <= idx && !tracker.splits(&chunk_type),
+                ChunkPosition::AfterIhdr => false,
+            }
+        {
+            inserted = insert(writer, idx - held_fctl.is_some() as usize)?;
+        }
+
+        if let Some(chunk) = held_fctl.take() {
+            writer.write_chunk(&chunk)?;
+        }
+
+        if inserted.is_none() && position == ChunkPosition::BeforeIdat && chunk_type == "fcTL" {
+            held_fctl = Some(reader.read_chunk()?);
+        } else {
+            reader.copy_chunk(writer)?;
+        }
+
+        tracker.push(&chunk_type);
+        seen_iend |= chunk_type == "IEND";
+        idx += 1;
+
+        if inserted.is_none() && position == ChunkPosition::AfterIhdr && chunk_type == "IHDR" {
+            inserted = insert(writer, idx)?;
+        }
+    }
+
+    if let Some(chunk) = held_fctl.take() {
+        writer.write_chunk(&chunk)?;
+    }
+
+    match (inserted, position) {
+        (Some(at), _) => Ok(at),
+        (None, ChunkPosition::BeforeIend) => insert(writer, idx).map(|_| idx),
+        (None, ChunkPosition::Index(at)) if seen_iend => Err(png::outside_image(at)),
+        (None, ChunkPosition::Index(at)) if at <= idx => insert(writer, idx).map(|_| idx),
+        (None, ChunkPosition::Index(at)) => Err(Error::IndexOutOfRange {
+            index: at,
+            len: idx,
+        }),
+        (None, ChunkPosition::AfterIhdr) => Err(Error::ChunkNotFound("IHDR".into())),
+        (None, ChunkPosition::BeforeIdat) => Err(Error::ChunkNotFound("IDAT".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn testing_png() -> Vec<u8> {
+        let chunks = ["IHDR", "IDAT", "IDAT", "IEND"]
+            .iter()
+            .map(|t| Chunk::new(ChunkType::from_str(t).unwrap(), t.as_bytes().to_vec()))
+            .collect();
+
+        Png::from_chunks(chunks).as_bytes()
+    }
+
+    fn testing_chunk() -> Chunk {
+        Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"Message".to_vec())
+    }
+
+    fn insert(position: ChunkPosition) -> Result<(usize, Png), Error> {
+        let bytes = testing_png();
+        let mut reader = PngReader::new(bytes.as_slice())?;
+        let mut writer = PngWriter::new(Vec::new())?;
+        let idx = insert_chunks(&mut reader, &mut writer, &[testing_chunk()], position)?;
+        let png = Png::try_from(writer.finish()?.as_slice())?;
+
+        Ok((idx, png))
+    }
+
+    #[test]
+    fn test_read_chunks() {
+        let bytes = testing_png();
+        let mut reader = PngReader::new(bytes.as_slice()).unwrap();
+
+        let header = reader.next_header().unwrap().unwrap();
+        assert_eq!(header.chunk_type.to_string(), "IHDR");
+        assert_eq!(header.length, 4);
+        assert_eq!(reader.read_chunk().unwrap().data(), b"IHDR");
+
+        // An unread chunk is skipped by the next header.
+        reader.next_header().unwrap().unwrap();
+        let header = reader.next_header().unwrap().unwrap();
+        assert_eq!(header.chunk_type.to_string(), "IDAT");
+        reader.skip_chunk().unwrap();
+
+        assert_eq!(
+            reader
+                .next_header()
+                .unwrap()
+                .unwrap()
+                .chunk_type
+                .to_string(),
+            "IEND"
+        );
+        assert!(reader.next_header().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_copy_chunks() {
+        let bytes = testing_png();
+        let mut reader = PngReader::new(bytes.as_slice()).unwrap();
+        let mut writer = PngWriter::new(Vec::new()).unwrap();
+
+        while reader.next_header().unwrap().is_some() {
+            reader.copy_chunk(&mut writer).unwrap();
+        }
+
+        assert_eq!(writer.finish().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_bad_crc() {
+        let mut bytes = testing_png();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let mut reader = PngReader::new(bytes.as_slice()).unwrap();
+
+        let res = (0..4).try_for_each(|_| {
+            reader.next_header()?;
+            reader.skip_chunk()
+        });
+        assert!(matches!(res.unwrap_err().root(), Error::CrcMismatch { .. }));
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = testing_png();
+        let mut reader = PngReader::new(&bytes[..bytes.len() - 2]).unwrap();
+
+        let res = (0..4).try_for_each(|_| {
+            reader.next_header()?;
+            reader.skip_chunk()
+        });
+        let err = res.unwrap_err();
+        assert!(err.is_truncated());
+        assert!(matches!(
+            err,
+            Error::InChunk {
+                chunk_index: 3,
+                offset: 56,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_length_past_the_end() {
+        let mut bytes = testing_png();
+        // IHDR claims 2^31 - 1 bytes of data but the file is much shorter.
+        bytes[8..12].copy_from_slice(&MAX_LENGTH.to_be_bytes());
+        let mut reader = PngReader::new(bytes.as_slice()).unwrap();
+
+        reader.next_header().unwrap().unwrap();
+        assert!(reader.read_chunk().unwrap_err().is_truncated());
+
+        bytes[8..12].copy_from_slice(&(MAX_LENGTH + 1).to_be_bytes());
+        let mut reader = PngReader::new(bytes.as_slice()).unwrap();
+
+        assert!(matches!(
+            reader.next_header().unwrap_err(),
+            Error::InChunk { source, .. } if matches!(*source, Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        assert!(PngReader::new([0u8; 8].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_insert_chunks_matches_png() {
+        for position in [
+            ChunkPosition::BeforeIend,
+            ChunkPosition::AfterIhdr,
+            ChunkPosition::BeforeIdat,
+            ChunkPosition::Index(1),
+            ChunkPosition::Index(3),
+        ] {
+            let (idx, streamed) = insert(position).unwrap();
+            let mut expected = Png::try_from(testing_png().as_slice()).unwrap();
+            let expected_idx = expected.insert_chunk(testing_chunk(), position).unwrap();
+
+            assert_eq!(idx, expected_idx);
+            assert_eq!(streamed.as_bytes(), expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_insert_chunks_keeps_frames() {
+        let chunks = [
+            "IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "fdAT", "IEND",
+        ]
+        .iter()
+        .map(|t| Chunk::new(ChunkType::from_str(t).unwrap(), t.as_bytes().to_vec()))
+        .collect();
+        let bytes = Png::from_chunks(chunks).as_bytes();
+
+        for (position, expected_idx) in [
+            (ChunkPosition::BeforeIdat, 2),
+            (ChunkPosition::Index(3), 4),
+            (ChunkPosition::Index(5), 7),
+            (ChunkPosition::Index(4), 4),
+            (ChunkPosition::BeforeIend, 7),
+        ] {
+            let mut reader = PngReader::new(bytes.as_slice()).unwrap();
+            let mut writer = PngWriter::new(Vec::new()).unwrap();
+            let idx =
+                insert_chunks(&mut reader, &mut writer, &[testing_chunk()], position).unwrap();
+            let streamed = Png::try_from(writer.finish().unwrap().as_slice()).unwrap();
+            let mut expected = Png::try_from(bytes.as_slice()).unwrap();
+
+            assert_eq!(idx, expected_idx);
+            assert_eq!(
+                expected.insert_chunk(testing_chunk(), position).unwrap(),
+                idx
+            );
+            assert_eq!(streamed.as_bytes(), expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_insert_chunks_out_of_range() {
+        assert!(matches!(
+            insert(ChunkPosition::Index(5)),
+            Err(Error::InvalidInput(_))
+        ));
+        // Before IHDR and after IEND.
+        assert!(insert(ChunkPosition::Index(0)).is_err());
+        assert!(insert(ChunkPosition::Index(4)).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    error::Error,
+    optimize::{self, FilterStrategy, Raster},
+    png::Png,
+};
+
+/// Ancillary chunks kept by [`strip`], the ones that change how the pixels look, in the order
+/// it writes them. `tRNS` has to follow `PLTE`, the others have to come before it.
+pub const KEPT_ANCILLARY: [&str; 7] = ["gAMA", "cHRM", "sRGB", "iCCP", "sBIT", "cICP", "tRNS"];
+/// Largest `IDAT` chunk written by [`strip`] unless asked otherwise.
+pub const IDAT_LEN: usize = 64 * 1024;
+
+pub struct Stripped {
+    pub png: Png,
+    /// The dropped chunks, in file order.
+    pub removed: Vec<Chunk>,
+}
+
+/// Where `chunk_type` goes in the output of [`strip`]: `IHDR`, the [`KEPT_ANCILLARY`] chunks
+/// with `PLTE` before `tRNS`, the `keep` types in their given order, then `IDAT` and `IEND`.
+fn canonical_rank(chunk_type: &ChunkType, keep: &[ChunkType]) -> usize {
+    let name = chunk_type.to_string();
+    let kept = KEPT_ANCILLARY.iter().position(|&t| t == name);
+    let tail = KEPT_ANCILLARY.len() + 2;
+
+    match name.as_str() {
+        "IHDR" => 0,
+        "PLTE" => KEPT_ANCILLARY.len(),
+        "tRNS" => KEPT_ANCILLARY.len() + 1,
+        "IDAT" => tail + keep.len() + 1,
+        "IEND" => tail + keep.len() + 2,
+        _ => match (kept, keep.iter().position(|t| t == chunk_type)) {
+            (Some(idx), _) => idx + 1,
+            (None, Some(idx)) => tail + idx,
+            // Any other critical chunk goes right before the image data.
+            (None, None) => tail + keep.len(),
+        },
+    }
+}
+
+/// Drop every ancillary chunk that is neither in [`KEPT_ANCILLARY`] nor in `keep`, and encode
+/// the image data again so the same pixels always give the same bytes.
+///
+/// Every row is filtered again with a fixed heuristic, the result is deflated at the best level
+/// and spread over evenly sized `IDAT` chunks of at most `idat_len` bytes. The kept chunks are
+/// put in a fixed order whatever their order in `png`, chunks of the same type keep theirs.
+pub fn strip(png: &Png, keep: &[ChunkType], idat_len: usize) -> Result<Stripped, Error> {
+    if idat_len == 0 {
+        return Err(Error::InvalidInput("IDAT size must not be 0".into()));
+    }
+
+    let strategy = FilterStrategy::recommended(&png.ihdr()?);
+    let data = optimize::deflate(&Raster::from_png(png)?.filter(strategy), 9)?;
+    let (kept, removed): (Vec<Chunk>, Vec<Chunk>) = png.chunks().iter().cloned().partition(|c| {
+        let chunk_type = c.chunk_type();
+
+        chunk_type.is_critical()
+            || KEPT_ANCILLARY.contains(&chunk_type.to_string().as_str())
+            || keep.contains(chunk_type)
+    });
+    let mut kept = kept;
+
+    kept.sort_by_key(|c| canonical_rank(c.chunk_type(), keep));
+
+    let mut png = Png::from_chunks(kept);
+
+    png.set_image_data(&data, optimize::even_chunk_len(data.len(), idat_len))?;
+
+    Ok(Stripped { png, removed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ihdr::{ColorType, Ihdr},
+        scanline,
+    };
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::{io::Write, str::FromStr};
+
+    fn testing_ihdr(interlace: u8) -> Ihdr {
+        Ihdr {
+            width: 37,
+            height: 21,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            compression: 0,
+            filter: 0,
+            interlace,
+        }
+    }
+
+    /// The same pixels, filtered with `filter_type`, deflated at `level` and split every
+    /// `split` bytes, with text chunks around them.
+    fn testing_png(interlace: u8, filter_type: u8, level: u32, split: usize) -> Png {
+        let ihdr = testing_ihdr(interlace);
+        let bpp = ihdr.filter_bpp();
+        let mut filtered = Vec::new();
+        let mut seed = 7u32;
+
+        for (stride, rows) in ihdr.passes() {
+            let raw: Vec<u8> = (0..stride * rows)
+                .map(|i| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    (i as u32 / 3 + (seed >> 28)) as u8
+                })
+                .collect();
+
+            filtered.extend(scanline::filter(
+                &raw,
+                stride,
+                bpp,
+                &vec![filter_type; rows],
+            ));
+        }
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+        encoder.write_all(&filtered).unwrap();
+
+        let chunk = |chunk_type: &str, data: &[u8]| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+        };
+
+        Png::builder()
+            .ihdr(&ihdr)
+            .chunk(chunk("gAMA", &45455u32.to_be_bytes()))
+            .chunk(chunk("tEXt", b"Author\0someone"))
+            .chunks(
+                encoder
+                    .finish()
+                    .unwrap()
+                    .chunks(split)
+                    .map(|part| chunk("IDAT", part)),
+            )
+            .chunk(chunk("tIME", &[7, 234, 1, 2, 3, 4, 5]))
+            .build()
+    }
+
+    #[test]
+    fn test_strip() {
+        let png = testing_png(0, scanline::FILTER_NONE, 6, 100);
+        let stripped = strip(&png, &[], 300).unwrap();
+        let types: Vec<String> = stripped
+            .png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        let removed: Vec<String> = stripped
+            .removed
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+
+        assert_eq!(removed, ["tEXt", "tIME"]);
+        assert_eq!(types[..2], ["IHDR", "gAMA"]);
+        assert_eq!(types.last().unwrap(), "IEND");
+
+        let lengths: Vec<u32> = stripped
+            .png
+            .chunks_by_type("IDAT")
+            .iter()
+            .map(|c| c.length())
+            .collect();
+        let (last, rest) = lengths.split_last().unwrap();
+
+        assert!(rest.iter().all(|&len| len == lengths[0] && len <= 300));
+        assert!(*last <= lengths[0] && lengths[0] - last < lengths.len() as u32);
+    }
+
+    #[test]
+    fn test_strip_keep() {
+        let png = testing_png(0, scanline::FILTER_NONE, 6, 100);
+        let stripped = strip(&png, &[ChunkType::from_str("tIME").unwrap()], IDAT_LEN).unwrap();
+
+        assert_eq!(stripped.removed.len(), 1);
+        assert!(stripped.png.chunk_by_type("tIME").is_some());
+        assert_eq!(stripped.png.chunks_by_type("IDAT").len(), 1);
+        assert!(strip(&png, &[], 0).is_err());
+    }
+
+    #[test]
+    fn test_strip_is_deterministic() {
+        for interlace in [0, 1] {
+            let expected = strip(
+                &testing_png(interlace, scanline::FILTER_NONE, 1, 50),
+                &[],
+                500,
+            )
+            .unwrap()
+            .png
+            .as_bytes();
+
+            for (filter_type, level, split) in [
+                (scanline::FILTER_PAETH, 9, 1000),
+                (scanline::FILTER_SUB, 0, 64),
+                (scanline::FILTER_AVERAGE, 6, 7),
+            ] {
+                let png = testing_png(interlace, filter_type, level, split);
+
+                assert_ne!(png.as_bytes(), expected);
+                assert_eq!(strip(&png, &[], 500).unwrap().png.as_bytes(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_strip_canonical_order() {
+        let png = testing_png(0, scanline::FILTER_NONE, 6, 100);
+        let by_type = |chunk_type: &str| -> Vec<Chunk> {
+            png.chunks()
+                .iter()
+                .filter(|c| c.chunk_type().to_string() == chunk_type)
+                .cloned()
+                .collect()
+        };
+        let chunk = |chunk_type: &str, data: &[u8]| {
+            vec![Chunk::new(
+                ChunkType::from_str(chunk_type).unwrap(),
+                data.to_vec(),
+            )]
+        };
+        let keep = [
+            ChunkType::from_str("tIME").unwrap(),
+            ChunkType::from_str("pHYs").unwrap(),
+        ];
+        let srgb = chunk("sRGB", &[0]);
+        let phys = chunk("pHYs", &[0, 0, 0, 1, 0, 0, 0, 1, 0]);
+        let before = Png::from_chunks(
+            [
+                by_type("IHDR"),
+                srgb.clone(),
+                by_type("gAMA"),
+                phys.clone(),
+                by_type("IDAT"),
+                by_type("tIME"),
+                by_type("IEND"),
+            ]
+            .concat(),
+        );
+        let after = Png::from_chunks(
+            [
+                by_type("IHDR"),
+                by_type("tIME"),
+                phys,
+                by_type("gAMA"),
+                srgb,
+                by_type("IDAT"),
+                by_type("IEND"),
+            ]
+            .concat(),
+        );
+
+        let expected = strip(&before, &keep, IDAT_LEN).unwrap().png;
+        let types: Vec<String> = expected
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+
+        assert_eq!(
+            types,
+            ["IHDR", "gAMA", "sRGB", "tIME", "pHYs", "IDAT", "IEND"]
+        );
+        assert_eq!(
+            strip(&after, &keep, IDAT_LEN).unwrap().png.as_bytes(),
+            expected.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_strip_short_image_data() {
+        let mut png = testing_png(1, scanline::FILTER_NONE, 6, 100);
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0; 10]).unwrap();
+        png.set_image_data(&encoder.finish().unwrap(), 100).unwrap();
+
+        assert!(matches!(
+            strip(&png, &[], IDAT_LEN),
+            Err(Error::InvalidData(_))
+        ));
+    }
+}
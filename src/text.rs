@@ -0,0 +1,337 @@
+use crate::{chunk::Chunk, chunk_type::ChunkType, error::Error};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+
+/// Chunk types that carry text entries.
+pub const TEXT_CHUNK_TYPES: [&str; 3] = ["tEXt", "zTXt", "iTXt"];
+/// Compressed text is not inflated past this many bytes, so a small chunk can not blow up.
+pub const MAX_INFLATED_LEN: u64 = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKind {
+    /// `tEXt`, Latin-1 text.
+    Text,
+    /// `zTXt`, zlib compressed Latin-1 text.
+    Compressed,
+    /// `iTXt`, UTF-8 text with a language tag, optionally compressed.
+    International,
+}
+
+impl TextKind {
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            Self::Text => "tEXt",
+            Self::Compressed => "zTXt",
+            Self::International => "iTXt",
+        }
+    }
+}
+
+impl FromStr for TextKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "ztxt" => Ok(Self::Compressed),
+            "itxt" => Ok(Self::International),
+            _ => Err(Error::InvalidInput(format!(
+                "Unsupported text chunk type: {}, expect tEXt, zTXt or iTXt",
+                s
+            ))),
+        }
+    }
+}
+
+/// A keyword/value pair stored in a `tEXt`, `zTXt` or `iTXt` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEntry {
+    pub kind: TextKind,
+    pub keyword: String,
+    pub text: String,
+    /// Only meaningful for `iTXt`, `zTXt` is always compressed.
+    pub compressed: bool,
+    /// Only meaningful for `iTXt`.
+    pub language_tag: String,
+    /// Only meaningful for `iTXt`.
+    pub translated_keyword: String,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidData(message.into())
+}
+
+fn latin1_to_string(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn string_to_latin1(s: &str) -> Result<Vec<u8>, Error> {
+    s.chars()
+        .map(|c| u8::try_from(c as u32).map_err(|_| invalid("Text is not Latin-1")))
+        .collect()
+}
+
+fn inflate(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut inflated = Vec::new();
+
+    // Read one byte past the limit so text that inflates further is caught.
+    ZlibDecoder::new(bytes)
+        .take(MAX_INFLATED_LEN + 1)
+        .read_to_end(&mut inflated)?;
+
+    if inflated.len() as u64 > MAX_INFLATED_LEN {
+        return Err(Error::InvalidData(format!(
+            "Compressed text inflates past {} byte(s)",
+            MAX_INFLATED_LEN
+        )));
+    }
+
+    Ok(inflated)
+}
+
+fn deflate(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+
+    encoder.write_all(bytes)?;
+
+    Ok(encoder.finish()?)
+}
+
+/// Split `bytes` at the first null byte.
+fn split_null(bytes: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    match bytes.iter().position(|&b| b == 0) {
+        Some(idx) => Ok((&bytes[..idx], &bytes[idx + 1..])),
+        None => Err(invalid("Missing null separator")),
+    }
+}
+
+fn check_keyword(keyword: &[u8]) -> Result<(), Error> {
+    if keyword.is_empty() || keyword.len() > 79 {
+        return Err(invalid("Keyword must be 1 to 79 bytes"));
+    }
+
+    if keyword.starts_with(b" ")
+        || keyword.ends_with(b" ")
+        || keyword.windows(2).any(|w| w == b"  ")
+    {
+        return Err(invalid(
+            "Keyword must not have leading, trailing or consecutive spaces",
+        ));
+    }
+
+    if !keyword.iter().all(|&b| (32..=126).contains(&b) || b >= 161) {
+        return Err(invalid("Keyword has a non printable character"));
+    }
+
+    Ok(())
+}
+
+impl TryFrom<&Chunk> for TextEntry {
+    type Error = Error;
+
+    fn try_from(value: &Chunk) -> Result<Self, Self::Error> {
+        let (keyword, rest) = split_null(value.data())?;
+
+        check_keyword(keyword)?;
+
+        let keyword = latin1_to_string(keyword);
+
+        match value.chunk_type().to_string().as_str() {
+            "tEXt" => Ok(Self::new(TextKind::Text, keyword, latin1_to_string(rest))),
+            "zTXt" => match rest.split_first() {
+                Some((0, data)) => Ok(Self::new(
+                    TextKind::Compressed,
+                    keyword,
+                    latin1_to_string(&inflate(data)?),
+                )),
+                Some(_) => Err(invalid("Unknown compression method")),
+                None => Err(invalid("zTXt is truncated")),
+            },
+            "iTXt" => {
+                let (compressed, method, rest) = match rest {
+                    [flag, method, rest @ ..] => (*flag, *method, rest),
+                    _ => return Err(invalid("iTXt is truncated")),
+                };
+                let (language_tag, rest) = split_null(rest)?;
+                let (translated_keyword, text) = split_null(rest)?;
+                let text = match (compressed, method) {
+                    (0, _) => text.to_vec(),
+                    (1, 0) => inflate(text)?,
+                    (1, _) => return Err(invalid("Unknown compression method")),
+                    _ => return Err(invalid("Invalid compression flag")),
+                };
+                let utf8 = |bytes: Vec<u8>| {
+                    String::from_utf8(bytes).map_err(|_| invalid("iTXt is not valid UTF-8"))
+                };
+
+                Ok(Self {
+                    kind: TextKind::International,
+                    keyword,
+                    text: utf8(text)?,
+                    compressed: compressed == 1,
+                    language_tag: utf8(language_tag.to_vec())?,
+                    translated_keyword: utf8(translated_keyword.to_vec())?,
+                })
+            }
+            t => Err(Error::InvalidInput(format!("{} is not a text chunk", t))),
+        }
+    }
+}
+
+impl std::fmt::Display for TextEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind.chunk_type(), self.keyword)?;
+
+        if !self.language_tag.is_empty() || !self.translated_keyword.is_empty() {
+            write!(f, " [{}] {}", self.language_tag, self.translated_keyword)?;
+        }
+
+        write!(f, ": {}", self.text)
+    }
+}
+
+impl TextEntry {
+    pub fn new(kind: TextKind, keyword: String, text: String) -> TextEntry {
+        Self {
+            kind,
+            keyword,
+            text,
+            compressed: kind == TextKind::Compressed,
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+        }
+    }
+
+    pub fn is_text_chunk(chunk: &Chunk) -> bool {
+        TEXT_CHUNK_TYPES.contains(&chunk.chunk_type().to_string().as_str())
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk, Error> {
+        let keyword = string_to_latin1(&self.keyword)?;
+
+        check_keyword(&keyword)?;
+
+        let data = match self.kind {
+            TextKind::Text => [keyword.as_slice(), &[0], &string_to_latin1(&self.text)?].concat(),
+            TextKind::Compressed => [
+                keyword.as_slice(),
+                &[0, 0],
+                &deflate(&string_to_latin1(&self.text)?)?,
+            ]
+            .concat(),
+            TextKind::International => [
+                keyword.as_slice(),
+                &[0, self.compressed as u8, 0],
+                self.language_tag.as_bytes(),
+                &[0],
+                self.translated_keyword.as_bytes(),
+                &[0],
+                &if self.compressed {
+                    deflate(self.text.as_bytes())?
+                } else {
+                    self.text.as_bytes().to_vec()
+                },
+            ]
+            .concat(),
+        };
+
+        Ok(Chunk::new(
+            ChunkType::from_str(self.kind.chunk_type())?,
+            data,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(entry: &TextEntry) -> TextEntry {
+        TextEntry::try_from(&entry.to_chunk().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_text() {
+        let entry = TextEntry::new(TextKind::Text, "Author".into(), "Zoë".into());
+        let chunk = entry.to_chunk().unwrap();
+        assert_eq!(chunk.data(), b"Author\0Zo\xeb");
+        assert_eq!(round_trip(&entry), entry);
+    }
+
+    #[test]
+    fn test_compressed_text() {
+        let entry = TextEntry::new(TextKind::Compressed, "Comment".into(), "a".repeat(200));
+        let chunk = entry.to_chunk().unwrap();
+        assert!(chunk.length() < 100);
+        assert_eq!(round_trip(&entry), entry);
+    }
+
+    #[test]
+    fn test_international_text() {
+        for compressed in [false, true] {
+            let entry = TextEntry {
+                compressed,
+                language_tag: "ja".into(),
+                translated_keyword: "作者".into(),
+                ..TextEntry::new(TextKind::International, "Author".into(), "山田".into())
+            };
+            assert_eq!(round_trip(&entry), entry);
+        }
+    }
+
+    #[test]
+    fn test_compressed_text_too_long() {
+        let text = vec![b'a'; MAX_INFLATED_LEN as usize + 1];
+        let data = [b"Comment\0\0".as_slice(), &deflate(&text).unwrap()].concat();
+        let chunk = Chunk::new(ChunkType::from_str("zTXt").unwrap(), data);
+
+        assert!(matches!(
+            TextEntry::try_from(&chunk),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_text_kind_from_str() {
+        assert_eq!(TextKind::from_str("tEXt").unwrap(), TextKind::Text);
+        assert_eq!(TextKind::from_str("ztxt").unwrap(), TextKind::Compressed);
+        assert_eq!(TextKind::from_str("iTXt").unwrap(), TextKind::International);
+        assert!(TextKind::from_str("RuSt").is_err());
+    }
+
+    #[test]
+    fn test_invalid_keyword() {
+        for keyword in ["", " lead", "trail ", "two  spaces", &"k".repeat(80)] {
+            let entry = TextEntry::new(TextKind::Text, keyword.into(), "text".into());
+            assert!(entry.to_chunk().is_err());
+        }
+    }
+
+    #[test]
+    fn test_not_latin1() {
+        let entry = TextEntry::new(TextKind::Text, "Author".into(), "山田".into());
+        assert!(entry.to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_invalid_chunks() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("tEXt").unwrap(),
+            b"no separator".to_vec(),
+        );
+        assert!(TextEntry::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(
+            ChunkType::from_str("zTXt").unwrap(),
+            b"key\0\x01abc".to_vec(),
+        );
+        assert!(TextEntry::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"key\0value".to_vec());
+        assert!(TextEntry::try_from(&chunk).is_err());
+        assert!(!TextEntry::is_text_chunk(&chunk));
+    }
+}
@@ -0,0 +1,354 @@
+use crate::{
+    chunk::Chunk,
+    ihdr::{ColorType, Ihdr},
+};
+
+/// Chunk types that may appear at most once.
+const SINGLETONS: [&str; 14] = [
+    "IHDR", "PLTE", "IEND", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "bKGD", "hIST", "tRNS", "pHYs",
+    "tIME", "eXIf",
+];
+/// Chunk types that must come before `PLTE` and `IDAT`.
+const BEFORE_PLTE: [&str; 5] = ["cHRM", "gAMA", "iCCP", "sBIT", "sRGB"];
+/// Chunk types that must come after `PLTE` and before `IDAT`.
+const AFTER_PLTE: [&str; 3] = ["tRNS", "bKGD", "hIST"];
+/// Chunk types that must come before `IDAT`.
+const BEFORE_IDAT: [&str; 2] = ["pHYs", "sPLT"];
+/// Critical chunk types known to this crate.
+const KNOWN_CRITICAL: [&str; 4] = ["IHDR", "PLTE", "IDAT", "IEND"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// One problem found in the chunk layout of a PNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Index of the offending chunk, `None` when the problem is a missing chunk.
+    pub chunk_index: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.chunk_index {
+            Some(idx) => write!(f, "{} [chunk {}]: {}", self.severity, idx, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+impl Diagnostic {
+    pub(crate) fn error(chunk_index: Option<usize>, message: String) -> Diagnostic {
+        Self {
+            severity: Severity::Error,
+            chunk_index,
+            message,
+        }
+    }
+
+    pub(crate) fn warning(chunk_index: Option<usize>, message: String) -> Diagnostic {
+        Self {
+            severity: Severity::Warning,
+            chunk_index,
+            message,
+        }
+    }
+}
+
+/// Check the chunk ordering rules of the PNG spec.
+pub fn validate(chunks: &[Chunk]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+    let first_of = |t: &str| types.iter().position(|x| x == t);
+
+    if chunks.is_empty() {
+        diagnostics.push(Diagnostic::error(None, "PNG has no chunks".into()));
+        return diagnostics;
+    }
+
+    let mut color_type = None;
+
+    match types[0].as_str() {
+        "IHDR" => match Ihdr::try_from(&chunks[0]) {
+            Ok(ihdr) => color_type = Some(ihdr.color_type),
+            Err(err) => diagnostics.push(Diagnostic::error(Some(0), err.to_string())),
+        },
+        _ if first_of("IHDR").is_none() => {
+            diagnostics.push(Diagnostic::error(None, "IHDR is missing".into()))
+        }
+        t => diagnostics.push(Diagnostic::error(
+            Some(0),
+            format!("First chunk is {}, expect IHDR", t),
+        )),
+    }
+
+    for (idx, (chunk, t)) in chunks.iter().zip(&types).enumerate() {
+        let c_type = chunk.chunk_type();
+
+        if !c_type.is_reserved_bit_valid() {
+            diagnostics.push(Diagnostic::error(
+                Some(idx),
+                format!("{} has the reserved bit set", t),
+            ));
+        }
+
+        if c_type.is_critical() && !KNOWN_CRITICAL.contains(&t.as_str()) {
+            diagnostics.push(Diagnostic::warning(
+                Some(idx),
+                format!("{} is an unknown critical chunk", t),
+            ));
+        }
+
+        if t == "IHDR" && idx != 0 {
+            diagnostics.push(Diagnostic::error(
+                Some(idx),
+                "IHDR must be the first chunk".into(),
+            ));
+        }
+
+        if SINGLETONS.contains(&t.as_str()) && first_of(t) != Some(idx) {
+            let message = format!("{} must not appear more than once", t);
+
+            diagnostics.push(if c_type.is_critical() {
+                Diagnostic::error(Some(idx), message)
+            } else {
+                Diagnostic::warning(Some(idx), message)
+            });
+        }
+    }
+
+    match first_of("IEND") {
+        None => diagnostics.push(Diagnostic::error(None, "IEND is missing".into())),
+        Some(idx) => {
+            if idx + 1 != chunks.len() {
+                diagnostics.push(Diagnostic::error(
+                    Some(idx + 1),
+                    format!("{} chunk(s) after IEND", chunks.len() - idx - 1),
+                ));
+            }
+
+            if chunks[idx].length() != 0 {
+                diagnostics.push(Diagnostic::warning(
+                    Some(idx),
+                    "IEND should be empty".into(),
+                ));
+            }
+        }
+    }
+
+    let idat: Vec<usize> = (0..types.len()).filter(|&i| types[i] == "IDAT").collect();
+
+    match idat.first() {
+        None => diagnostics.push(Diagnostic::error(None, "IDAT is missing".into())),
+        Some(&first) => {
+            if let Some(gap) = idat.windows(2).find(|w| w[1] != w[0] + 1) {
+                diagnostics.push(Diagnostic::error(
+                    Some(gap[1]),
+                    format!(
+                        "IDAT is not consecutive, chunk {} breaks the run",
+                        gap[0] + 1
+                    ),
+                ));
+            }
+
+            for (idx, t) in types.iter().enumerate().skip(first) {
+                let must_precede = BEFORE_PLTE.contains(&t.as_str())
+                    || AFTER_PLTE.contains(&t.as_str())
+                    || BEFORE_IDAT.contains(&t.as_str())
+                    || t == "PLTE";
+
+                if must_precede {
+                    diagnostics.push(Diagnostic::error(
+                        Some(idx),
+                        format!("{} must come before IDAT", t),
+                    ));
+                }
+            }
+        }
+    }
+
+    match first_of("PLTE") {
+        Some(plte) => {
+            if matches!(
+                color_type,
+                Some(ColorType::Grayscale | ColorType::GrayscaleAlpha)
+            ) {
+                diagnostics.push(Diagnostic::error(
+                    Some(plte),
+                    "PLTE must not appear in grayscale images".into(),
+                ));
+            }
+
+            for (idx, t) in types.iter().enumerate() {
+                if idx > plte && BEFORE_PLTE.contains(&t.as_str()) {
+                    diagnostics.push(Diagnostic::error(
+                        Some(idx),
+                        format!("{} must come before PLTE", t),
+                    ));
+                }
+
+                if idx < plte && AFTER_PLTE.contains(&t.as_str()) {
+                    diagnostics.push(Diagnostic::error(
+                        Some(idx),
+                        format!("{} must come after PLTE", t),
+                    ));
+                }
+            }
+        }
+        None if color_type == Some(ColorType::Indexed) => diagnostics.push(Diagnostic::error(
+            None,
+            "PLTE is required for indexed images".into(),
+        )),
+        None => {
+            if let Some(idx) = first_of("hIST") {
+                diagnostics.push(Diagnostic::error(Some(idx), "hIST requires PLTE".into()));
+            }
+        }
+    }
+
+    if let (Some(_), Some(idx)) = (first_of("sRGB"), first_of("iCCP")) {
+        diagnostics.push(Diagnostic::warning(
+            Some(idx),
+            "iCCP and sRGB should not both be present".into(),
+        ));
+    }
+
+    diagnostics.extend(crate::apng::validate(chunks));
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn testing_chunks(types: &[&str]) -> Vec<Chunk> {
+        types
+            .iter()
+            .map(|&t| {
+                let data = match t {
+                    // 1x1 8-bit RGB
+                    "IHDR" => vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0],
+                    "IEND" => vec![],
+                    _ => vec![0],
+                };
+
+                Chunk::new(ChunkType::from_str(t).unwrap(), data)
+            })
+            .collect()
+    }
+
+    fn errors(types: &[&str]) -> Vec<Diagnostic> {
+        validate(&testing_chunks(types))
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid() {
+        assert!(validate(&testing_chunks(&[
+            "IHDR", "gAMA", "IDAT", "IDAT", "tEXt", "IEND"
+        ]))
+        .is_empty());
+        assert!(errors(&["IHDR", "PLTE", "tRNS", "IDAT", "IEND"]).is_empty());
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(errors(&[]).len(), 1);
+    }
+
+    #[test]
+    fn test_ihdr_not_first() {
+        let diagnostics = errors(&["gAMA", "IHDR", "IDAT", "IEND"]);
+        assert_eq!(diagnostics[0].chunk_index, Some(0));
+        assert!(diagnostics.iter().any(|d| d.chunk_index == Some(1)));
+
+        let diagnostics = errors(&["IDAT", "IEND"]);
+        assert_eq!(diagnostics[0].message, "IHDR is missing");
+    }
+
+    #[test]
+    fn test_missing_iend() {
+        let diagnostics = errors(&["IHDR", "IDAT"]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].chunk_index, None);
+    }
+
+    #[test]
+    fn test_chunk_after_iend() {
+        let diagnostics = errors(&["IHDR", "IDAT", "IEND", "tEXt"]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].chunk_index, Some(3));
+    }
+
+    #[test]
+    fn test_idat_gap() {
+        let diagnostics = errors(&["IHDR", "IDAT", "tEXt", "IDAT", "IEND"]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].chunk_index, Some(3));
+    }
+
+    #[test]
+    fn test_duplicated_singletons() {
+        let diagnostics = validate(&testing_chunks(&[
+            "IHDR", "gAMA", "gAMA", "IDAT", "IEND", "IEND",
+        ]));
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Warning && d.chunk_index == Some(2)));
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error && d.chunk_index == Some(5)));
+    }
+
+    #[test]
+    fn test_misplaced_plte() {
+        assert!(!errors(&["IHDR", "IDAT", "PLTE", "IEND"]).is_empty());
+        assert!(!errors(&["IHDR", "PLTE", "gAMA", "IDAT", "IEND"]).is_empty());
+        assert!(!errors(&["IHDR", "tRNS", "PLTE", "IDAT", "IEND"]).is_empty());
+        assert!(!errors(&["IHDR", "IDAT", "pHYs", "IEND"]).is_empty());
+    }
+
+    #[test]
+    fn test_plte_color_type() {
+        let mut chunks = testing_chunks(&["IHDR", "IDAT", "IEND"]);
+        // 1x1 8-bit indexed without PLTE
+        chunks[0] = Chunk::new(
+            ChunkType::from_str("IHDR").unwrap(),
+            vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0],
+        );
+        assert_eq!(validate(&chunks).len(), 1);
+
+        // 1x1 8-bit grayscale with PLTE
+        chunks[0] = Chunk::new(
+            ChunkType::from_str("IHDR").unwrap(),
+            vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0],
+        );
+        chunks.insert(1, testing_chunks(&["PLTE"]).remove(0));
+        assert_eq!(validate(&chunks).len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_ihdr() {
+        let mut chunks = testing_chunks(&["IHDR", "IDAT", "IEND"]);
+        chunks[0] = Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]);
+        assert_eq!(validate(&chunks)[0].chunk_index, Some(0));
+    }
+}